@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TYPE header_pair AS (
+    name TEXT,
+    value BYTEA
+);
+
+CREATE TABLE idempotency(
+    idempotency_key TEXT NOT NULL,
+    response_status_code SMALLINT NULL,
+    response_headers header_pair[] NULL,
+    response_body BYTEA NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (idempotency_key)
+);
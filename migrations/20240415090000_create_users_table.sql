@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE users(
+    user_id UUID PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Saved responses are a replay cache, so they can be dropped while keys become per user.
+DELETE FROM idempotency;
+ALTER TABLE idempotency ADD COLUMN user_id UUID NOT NULL REFERENCES users (user_id);
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_pkey;
+ALTER TABLE idempotency ADD PRIMARY KEY (user_id, idempotency_key);
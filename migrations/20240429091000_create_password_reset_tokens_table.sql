@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE password_reset_tokens(
+    token_hash TEXT NOT NULL,
+    user_id UUID NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (token_hash)
+);
@@ -0,0 +1,19 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN totp_secret TEXT,
+    ADD COLUMN totp_confirmed_at TIMESTAMPTZ,
+    ADD COLUMN totp_last_used_step BIGINT;
+
+CREATE TABLE recovery_codes(
+    user_id UUID NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ,
+    PRIMARY KEY (user_id, code_hash)
+);
+
+-- A single-row table holding settings that apply to every admin account.
+CREATE TABLE security_policy(
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    require_two_factor BOOLEAN NOT NULL
+);
+INSERT INTO security_policy(require_two_factor) VALUES (FALSE);
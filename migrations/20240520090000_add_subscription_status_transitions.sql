@@ -0,0 +1,21 @@
+-- Restrict subscriptions.status to the states known to the application.
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_status_check
+    CHECK (status IN (
+        'pending-confirmation', 'confirmed', 'unsubscribed', 'bounced', 'complained', 'suppressed'
+    ));
+
+CREATE TABLE subscription_status_transitions(
+    transition_id BIGSERIAL PRIMARY KEY,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    -- NULL for the transition that created the subscriber.
+    from_status TEXT NULL,
+    to_status TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    transitioned_at timestamptz NOT NULL
+);
+CREATE INDEX subscription_status_transitions_subscriber_id_idx
+    ON subscription_status_transitions (subscriber_id, transitioned_at);
+
+-- Existing subscribers get a single entry for the status they are in today.
+INSERT INTO subscription_status_transitions(subscriber_id, from_status, to_status, reason, transitioned_at)
+SELECT id, NULL, status, 'existing subscriber', subscribed_at FROM subscriptions;
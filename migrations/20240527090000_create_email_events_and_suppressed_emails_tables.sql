@@ -0,0 +1,18 @@
+-- Bounce, complaint and delivery notifications received from the email provider.
+CREATE TABLE email_events(
+    email_event_id BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    email TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    received_at timestamptz NOT NULL
+);
+CREATE INDEX email_events_email_idx ON email_events (email);
+
+-- Addresses that must not receive any more email. Stored lowercased.
+CREATE TABLE suppressed_emails(
+    email TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    source TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (email)
+);
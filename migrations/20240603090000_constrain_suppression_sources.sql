@@ -0,0 +1,2 @@
+ALTER TABLE suppressed_emails ADD CONSTRAINT suppressed_emails_source_check
+    CHECK (source IN ('manual', 'bounce', 'complaint', 'legal'));
@@ -0,0 +1,7 @@
+-- Email templates edited outside of a deploy. A row replaces the template file with the same
+-- name, e.g. `confirmation.html`.
+CREATE TABLE email_templates(
+    name TEXT NOT NULL PRIMARY KEY,
+    source TEXT NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
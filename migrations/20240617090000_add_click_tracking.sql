@@ -0,0 +1,12 @@
+-- Whether the links of an issue go through the click tracking redirect.
+ALTER TABLE newsletter_issues ADD COLUMN track_clicks BOOLEAN NOT NULL DEFAULT false;
+
+-- Every click on a tracked link.
+CREATE TABLE link_clicks(
+    link_click_id BIGSERIAL PRIMARY KEY,
+    newsletter_issue_id UUID NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id UUID NOT NULL REFERENCES subscriptions (id),
+    url TEXT NOT NULL,
+    clicked_at timestamptz NOT NULL
+);
+CREATE INDEX link_clicks_newsletter_issue_id_idx ON link_clicks (newsletter_issue_id);
@@ -0,0 +1,20 @@
+-- Every issue handed over to the email provider, the basis of its engagement stats.
+CREATE TABLE issue_deliveries(
+    newsletter_issue_id UUID NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id UUID NOT NULL REFERENCES subscriptions (id),
+    subscriber_email TEXT NOT NULL,
+    sent_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
+CREATE INDEX issue_deliveries_subscriber_id_idx ON issue_deliveries (subscriber_id, sent_at);
+
+-- Opens of an issue, as reported by its tracking image. Clients that block or prefetch images
+-- make these approximate.
+CREATE TABLE email_opens(
+    newsletter_issue_id UUID NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id UUID NOT NULL REFERENCES subscriptions (id),
+    n_opens INTEGER NOT NULL,
+    first_opened_at timestamptz NOT NULL,
+    last_opened_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
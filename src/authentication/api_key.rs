@@ -0,0 +1,303 @@
+use actix_web::dev::Payload;
+use actix_web::http::header::{HeaderMap, HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+use crate::authentication::token::{generate_token, hash_token};
+use crate::routes::error_chain_fmt;
+
+const API_KEY_PREFIX: &str = "nlk_";
+const API_KEY_SECRET_LENGTH: usize = 40;
+// Enough of the key to recognise it in a listing without making it usable.
+const DISPLAYED_PREFIX_LENGTH: usize = API_KEY_PREFIX.len() + 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum ApiScope {
+    #[serde(rename = "subscribers:read")]
+    SubscribersRead,
+    #[serde(rename = "subscribers:write")]
+    SubscribersWrite,
+    #[serde(rename = "newsletters:publish")]
+    NewslettersPublish,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::SubscribersRead => "subscribers:read",
+            ApiScope::SubscribersWrite => "subscribers:write",
+            ApiScope::NewslettersPublish => "newsletters:publish",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        [
+            ApiScope::SubscribersRead,
+            ApiScope::SubscribersWrite,
+            ApiScope::NewslettersPublish,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == scope)
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A valid, unrevoked API key presented as `Authorization: Bearer <key>`.
+///
+/// Use it as a handler argument to require a key, then call [`ApiKey::require_scope`]
+/// for the scope the handler needs.
+#[derive(Debug)]
+pub struct ApiKey {
+    pub api_key_id: Uuid,
+    pub user_id: Uuid,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl ApiKey {
+    pub fn require_scope(&self, scope: ApiScope) -> Result<(), ApiKeyError> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(ApiKeyError::MissingScope(scope))
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum ApiKeyError {
+    #[error("missing or malformed API key: {0}")]
+    MissingApiKey(String),
+    #[error("invalid or revoked API key")]
+    InvalidApiKey,
+    #[error("the API key lacks the `{0}` scope")]
+    MissingScope(ApiScope),
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl std::fmt::Debug for ApiKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ApiKeyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiKeyError::MissingApiKey(_) | ApiKeyError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            ApiKeyError::MissingScope(_) => StatusCode::FORBIDDEN,
+            ApiKeyError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self.status_code() {
+            StatusCode::UNAUTHORIZED => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                response
+                    .headers_mut()
+                    .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+                response
+            }
+            StatusCode::FORBIDDEN => HttpResponse::Forbidden().body(self.to_string()),
+            status_code => {
+                tracing::error!("{:?}", self);
+                HttpResponse::new(status_code)
+            }
+        }
+    }
+}
+
+impl FromRequest for ApiKey {
+    type Error = ApiKeyError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let headers = req.headers().clone();
+        let connection_pool = req
+            .app_data::<web::Data<PgPool>>()
+            .expect("the connection pool is registered as app data")
+            .clone();
+
+        Box::pin(async move { bearer_authentication(&headers, &connection_pool).await })
+    }
+}
+
+/// Whether the request carries bearer credentials, for endpoints that also accept other schemes.
+pub fn has_bearer_credentials(headers: &HeaderMap) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("Bearer "))
+}
+
+#[tracing::instrument(name = "Bearer authentication", skip_all, fields(api_key_id = tracing::field::Empty))]
+pub async fn bearer_authentication(
+    headers: &HeaderMap,
+    connection_pool: &PgPool,
+) -> Result<ApiKey, ApiKeyError> {
+    let key = headers
+        .get(AUTHORIZATION)
+        .ok_or_else(|| ApiKeyError::MissingApiKey("missing Authorization header".into()))?
+        .to_str()
+        .map_err(|_| ApiKeyError::MissingApiKey("Authorization header is not UTF8".into()))?
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| ApiKeyError::MissingApiKey("scheme is not 'Bearer'".into()))?;
+
+    // Looking the key up also records its use, so `last_used_at` costs no extra round trip.
+    let row = sqlx::query!(
+        r#"UPDATE api_keys SET last_used_at = now()
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        RETURNING api_key_id, user_id, scopes"#,
+        hash_token(&Secret::new(key.to_string()))
+    )
+    .fetch_optional(connection_pool)
+    .await?
+    .ok_or(ApiKeyError::InvalidApiKey)?;
+    tracing::Span::current().record("api_key_id", tracing::field::display(row.api_key_id));
+
+    Ok(ApiKey {
+        api_key_id: row.api_key_id,
+        user_id: row.user_id,
+        scopes: row
+            .scopes
+            .iter()
+            .filter_map(|scope| ApiScope::parse(scope))
+            .collect(),
+    })
+}
+
+pub struct NewApiKey {
+    pub api_key_id: Uuid,
+    /// The full key. It is not stored and cannot be shown again.
+    pub key: Secret<String>,
+}
+
+#[tracing::instrument(name = "Create API key", skip(connection_pool))]
+pub async fn create_api_key(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiScope],
+    connection_pool: &PgPool,
+) -> Result<NewApiKey, sqlx::Error> {
+    let api_key_id = Uuid::new_v4();
+    let key = Secret::new(format!(
+        "{}{}",
+        API_KEY_PREFIX,
+        generate_token(API_KEY_SECRET_LENGTH).expose_secret()
+    ));
+    let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+
+    sqlx::query!(
+        r#"INSERT INTO api_keys(
+            api_key_id, user_id, name, key_prefix, key_hash, scopes, created_at
+        )
+        VALUES($1, $2, $3, $4, $5, $6, now())"#,
+        api_key_id,
+        user_id,
+        name,
+        &key.expose_secret()[..DISPLAYED_PREFIX_LENGTH],
+        hash_token(&key),
+        &scopes
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(NewApiKey { api_key_id, key })
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiKeySummary {
+    pub api_key_id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(name = "List API keys", skip(connection_pool))]
+pub async fn list_api_keys(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<Vec<ApiKeySummary>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeySummary,
+        r#"SELECT api_key_id, name, key_prefix, scopes, created_at, last_used_at, revoked_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY created_at DESC"#,
+        user_id
+    )
+    .fetch_all(connection_pool)
+    .await
+}
+
+/// Revokes one of the user's keys. Returns `false` if the user has no such key.
+#[tracing::instrument(name = "Revoke API key", skip(connection_pool))]
+pub async fn revoke_api_key(
+    user_id: Uuid,
+    api_key_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let revoked = sqlx::query!(
+        r#"UPDATE api_keys SET revoked_at = COALESCE(revoked_at, now())
+        WHERE api_key_id = $1 AND user_id = $2"#,
+        api_key_id,
+        user_id
+    )
+    .execute(connection_pool)
+    .await?
+    .rows_affected();
+
+    Ok(revoked > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiKey, ApiKeyError, ApiScope};
+    use uuid::Uuid;
+
+    #[test]
+    fn scopes_round_trip_through_their_string_form() {
+        for scope in [
+            ApiScope::SubscribersRead,
+            ApiScope::SubscribersWrite,
+            ApiScope::NewslettersPublish,
+        ] {
+            assert_eq!(ApiScope::parse(scope.as_str()), Some(scope));
+            assert_eq!(
+                serde_json::to_string(&scope).unwrap(),
+                format!("\"{}\"", scope)
+            );
+        }
+        assert_eq!(ApiScope::parse("subscribers:delete"), None);
+    }
+
+    #[test]
+    fn require_scope_rejects_keys_without_it() {
+        let api_key = ApiKey {
+            api_key_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            scopes: vec![ApiScope::SubscribersRead],
+        };
+
+        assert!(api_key.require_scope(ApiScope::SubscribersRead).is_ok());
+        assert!(matches!(
+            api_key.require_scope(ApiScope::NewslettersPublish),
+            Err(ApiKeyError::MissingScope(ApiScope::NewslettersPublish))
+        ));
+    }
+}
@@ -0,0 +1,73 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::middleware::Next;
+use actix_web::{web, FromRequest, HttpMessage};
+use sqlx::PgPool;
+use std::ops::Deref;
+use uuid::Uuid;
+
+use crate::authentication::is_enrollment_required;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+#[derive(Copy, Clone, Debug)]
+pub struct UserId(Uuid);
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Deref for UserId {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub async fn reject_anonymous_users(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+
+    match session.get_user_id().map_err(e500)? {
+        Some(user_id) => {
+            // When the policy requires two-factor authentication, users who have not enrolled
+            // yet may only enroll or log out. This is checked on every request, so that it
+            // also applies to sessions opened before the policy was switched on.
+            if !is_allowed_before_enrollment(req.path()) {
+                let connection = req
+                    .app_data::<web::Data<PgPool>>()
+                    .expect("the connection pool is registered as app data");
+                if is_enrollment_required(user_id, connection)
+                    .await
+                    .map_err(e500)?
+                {
+                    let response = see_other("/admin/two_factor");
+                    let e =
+                        anyhow::anyhow!("The user has not enrolled in two-factor authentication");
+                    return Err(InternalError::from_response(e, response).into());
+                }
+            }
+
+            req.extensions_mut().insert(UserId(user_id));
+            next.call(req).await
+        }
+        None => {
+            let response = see_other("/login");
+            let e = anyhow::anyhow!("The user has not logged in");
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
+
+fn is_allowed_before_enrollment(path: &str) -> bool {
+    path == "/admin/logout" || path == "/admin/two_factor" || path.starts_with("/admin/two_factor/")
+}
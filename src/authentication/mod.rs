@@ -0,0 +1,29 @@
+mod api_key;
+mod middleware;
+mod password;
+mod password_policy;
+mod password_reset;
+mod token;
+mod totp;
+mod two_factor;
+
+pub use api_key::{
+    bearer_authentication, create_api_key, has_bearer_credentials, list_api_keys, revoke_api_key,
+    ApiKey, ApiKeyError, ApiKeySummary, ApiScope, NewApiKey,
+};
+pub use middleware::{reject_anonymous_users, UserId};
+pub use password::{
+    basic_authentication, change_password, compute_password_hash, create_user,
+    validate_configured_credentials, validate_credentials, AuthError, Credentials,
+};
+pub use password_policy::{NewPassword, PasswordPolicyError};
+pub use password_reset::{
+    create_password_reset_token, get_password_reset_recipient, reset_password,
+    validate_password_reset_token, PasswordResetRecipient, PASSWORD_RESET_TOKEN_TTL_MINUTES,
+};
+pub use totp::{provisioning_uri, totp_code, verify_totp_code};
+pub use two_factor::{
+    confirm_totp_enrollment, disable_two_factor, get_two_factor_status, is_enrollment_required,
+    is_second_factor_expected, is_two_factor_required, set_two_factor_required,
+    start_totp_enrollment, verify_second_factor, TwoFactorStatus,
+};
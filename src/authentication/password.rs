@@ -0,0 +1,285 @@
+use actix_web::http::header::HeaderMap;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use base64::Engine;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::token::constant_time_eq;
+use crate::authentication::NewPassword;
+use crate::domain::SubscriberEmail;
+use crate::routes::error_chain_fmt;
+use crate::telemetry::spawn_blocking_with_tracing;
+
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(thiserror::Error)]
+pub enum AuthError {
+    #[error("missing or malformed credentials: {0}")]
+    MissingCredentials(String),
+    #[error("invalid credentials")]
+    InvalidCredentials,
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("failed to hash the password")]
+    HashingError(#[from] argon2::password_hash::Error),
+    #[error("failed to run the password hashing task")]
+    TaskError(#[from] tokio::task::JoinError),
+}
+
+impl std::fmt::Debug for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+pub fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, AuthError> {
+    let header_value = headers
+        .get("Authorization")
+        .ok_or_else(|| AuthError::MissingCredentials("missing Authorization header".into()))?
+        .to_str()
+        .map_err(|_| AuthError::MissingCredentials("Authorization header is not UTF8".into()))?;
+    let base64_encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .ok_or_else(|| AuthError::MissingCredentials("scheme is not 'Basic'".into()))?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64_encoded_segment)
+        .map_err(|_| AuthError::MissingCredentials("credentials are not base64".into()))?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .map_err(|_| AuthError::MissingCredentials("credentials are not UTF8".into()))?;
+
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .ok_or_else(|| AuthError::MissingCredentials("missing ':' separator".into()))?;
+
+    Ok(Credentials {
+        username: username.to_string(),
+        password: Secret::new(password.to_string()),
+    })
+}
+
+/// Checks credentials against a fixed username and password from configuration rather
+/// than the users table, e.g. those the email provider sends along with its webhooks.
+pub fn validate_configured_credentials(
+    credentials: &Credentials,
+    username: &str,
+    password: &Secret<String>,
+) -> Result<(), AuthError> {
+    let username_matches = constant_time_eq(credentials.username.as_bytes(), username.as_bytes());
+    let password_matches = constant_time_eq(
+        credentials.password.expose_secret().as_bytes(),
+        password.expose_secret().as_bytes(),
+    );
+
+    if username_matches && password_matches {
+        Ok(())
+    } else {
+        Err(AuthError::InvalidCredentials)
+    }
+}
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, connection_pool))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    connection_pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let mut user_id = None;
+    // Unknown usernames are verified against a dummy hash with the same parameters, so the
+    // response time does not reveal which usernames exist.
+    let mut expected_password_hash = Secret::new(
+        "$argon2id$v=19$m=15000,t=2,p=1$\
+        gZiV/M1gPc22ElAH/Jh1Hw$\
+        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+            .to_string(),
+    );
+
+    if let Some((stored_user_id, stored_password_hash)) =
+        get_stored_credentials(&credentials.username, connection_pool).await?
+    {
+        user_id = Some(stored_user_id);
+        expected_password_hash = stored_password_hash;
+    }
+
+    spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, credentials.password)
+    })
+    .await??;
+
+    user_id.ok_or(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip(username, connection_pool))]
+async fn get_stored_credentials(
+    username: &str,
+    connection_pool: &PgPool,
+) -> Result<Option<(Uuid, Secret<String>)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT user_id, password_hash FROM users WHERE username = $1"#,
+        username,
+    )
+    .fetch_optional(connection_pool)
+    .await?
+    .map(|row| (row.user_id, Secret::new(row.password_hash)));
+
+    Ok(row)
+}
+
+#[tracing::instrument(
+    name = "Verify password hash",
+    skip(expected_password_hash, password_candidate)
+)]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())?;
+
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .map_err(|_| AuthError::InvalidCredentials)
+}
+
+pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, AuthError> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(15000, 2, 1, None).map_err(argon2::password_hash::Error::from)?,
+    )
+    .hash_password(password.expose_secret().as_bytes(), &salt)?
+    .to_string();
+
+    Ok(Secret::new(password_hash))
+}
+
+#[tracing::instrument(name = "Create user", skip(password, connection_pool))]
+pub async fn create_user(
+    username: &str,
+    email: Option<&SubscriberEmail>,
+    password: NewPassword,
+    connection_pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let user_id = Uuid::new_v4();
+    let password_hash = hash_new_password(password).await?;
+
+    sqlx::query!(
+        r#"INSERT INTO users(user_id, username, email, password_hash)
+        VALUES($1, $2, $3, $4)"#,
+        user_id,
+        username,
+        email.map(|email| email.as_ref()),
+        password_hash.expose_secret()
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Change password", skip(password, connection_pool))]
+pub async fn change_password(
+    user_id: Uuid,
+    password: NewPassword,
+    connection_pool: &PgPool,
+) -> Result<(), AuthError> {
+    let password_hash = hash_new_password(password).await?;
+
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(())
+}
+
+pub(crate) async fn hash_new_password(password: NewPassword) -> Result<Secret<String>, AuthError> {
+    spawn_blocking_with_tracing(move || compute_password_hash(password.into_secret())).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        basic_authentication, compute_password_hash, validate_configured_credentials,
+        verify_password_hash, AuthError, Credentials,
+    };
+    use actix_web::http::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+    use claims::assert_ok;
+    use secrecy::{ExposeSecret, Secret};
+
+    #[test]
+    fn a_computed_hash_verifies_the_original_password() {
+        let password = Secret::new("a-very-long-password".to_string());
+        let password_hash = compute_password_hash(password.clone()).unwrap();
+
+        assert!(password_hash.expose_secret().starts_with("$argon2id$"));
+        assert_ok!(verify_password_hash(password_hash, password));
+    }
+
+    #[test]
+    fn a_computed_hash_rejects_a_different_password() {
+        let password_hash =
+            compute_password_hash(Secret::new("a-very-long-password".to_string())).unwrap();
+
+        let result = verify_password_hash(password_hash, Secret::new("wrong".to_string()));
+        assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+    }
+
+    #[test]
+    fn basic_authentication_parses_username_and_password() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Basic dXNlcjpwYXNzOndvcmQ="),
+        );
+
+        let credentials = basic_authentication(&headers).unwrap();
+        assert_eq!(credentials.username, "user");
+        assert_eq!(credentials.password.expose_secret(), "pass:word");
+    }
+
+    #[test]
+    fn basic_authentication_rejects_other_schemes() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+
+        let result = basic_authentication(&headers);
+        assert!(matches!(result, Err(AuthError::MissingCredentials(_))));
+    }
+
+    #[test]
+    fn configured_credentials_must_match_exactly() {
+        let password = Secret::new("webhook-password".to_string());
+        let credentials = |username: &str, password: &str| Credentials {
+            username: username.to_string(),
+            password: Secret::new(password.to_string()),
+        };
+
+        assert_ok!(validate_configured_credentials(
+            &credentials("postmark", "webhook-password"),
+            "postmark",
+            &password
+        ));
+        for (username, attempt) in [
+            ("postmark", "webhook-passwor"),
+            ("postmark", "webhook-password2"),
+            ("Postmark", "webhook-password"),
+        ] {
+            let result = validate_configured_credentials(
+                &credentials(username, attempt),
+                "postmark",
+                &password,
+            );
+            assert!(matches!(result, Err(AuthError::InvalidCredentials)));
+        }
+    }
+}
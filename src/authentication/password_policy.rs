@@ -0,0 +1,98 @@
+use secrecy::{ExposeSecret, Secret};
+
+const MIN_LENGTH: usize = 12;
+const MAX_LENGTH: usize = 128;
+const MIN_CHARACTER_CLASSES: usize = 3;
+
+/// A password that satisfies the length and strength rules for admin accounts.
+#[derive(Debug)]
+pub struct NewPassword(Secret<String>);
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum PasswordPolicyError {
+    #[error("The new password must be at least {MIN_LENGTH} characters long.")]
+    TooShort,
+    #[error("The new password must be at most {MAX_LENGTH} characters long.")]
+    TooLong,
+    #[error(
+        "The new password must mix at least {MIN_CHARACTER_CLASSES} of: lowercase letters, \
+        uppercase letters, digits and symbols."
+    )]
+    TooWeak,
+}
+
+impl NewPassword {
+    pub fn parse(password: Secret<String>) -> Result<Self, PasswordPolicyError> {
+        let candidate = password.expose_secret();
+        let length = candidate.chars().count();
+
+        if length < MIN_LENGTH {
+            return Err(PasswordPolicyError::TooShort);
+        }
+        if length > MAX_LENGTH {
+            return Err(PasswordPolicyError::TooLong);
+        }
+
+        let character_classes = [
+            candidate.chars().any(|c| c.is_lowercase()),
+            candidate.chars().any(|c| c.is_uppercase()),
+            candidate.chars().any(|c| c.is_numeric()),
+            candidate.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if character_classes.iter().filter(|&&class| class).count() < MIN_CHARACTER_CLASSES {
+            return Err(PasswordPolicyError::TooWeak);
+        }
+
+        Ok(Self(password))
+    }
+
+    pub fn into_secret(self) -> Secret<String> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NewPassword, PasswordPolicyError};
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn parse(password: &str) -> Result<NewPassword, PasswordPolicyError> {
+        NewPassword::parse(Secret::new(password.to_string()))
+    }
+
+    #[test]
+    fn a_strong_password_is_accepted() {
+        assert_ok!(parse("Correct-Horse-Battery-9"));
+    }
+
+    #[test]
+    fn a_password_shorter_than_12_characters_is_rejected() {
+        assert_eq!(
+            assert_err!(parse("Sh0rt-pass")),
+            PasswordPolicyError::TooShort
+        );
+    }
+
+    #[test]
+    fn a_password_longer_than_128_characters_is_rejected() {
+        let password = format!("Aa1-{}", "x".repeat(125));
+        assert_eq!(assert_err!(parse(&password)), PasswordPolicyError::TooLong);
+    }
+
+    #[test]
+    fn length_is_measured_in_characters_not_bytes() {
+        assert_ok!(parse("ÄÖÜäöü-12345"));
+    }
+
+    #[test]
+    fn a_password_with_fewer_than_three_character_classes_is_rejected() {
+        for password in [
+            "alllowercaseletters",
+            "lowercase-and-symbols",
+            "UPPERCASE123456",
+        ] {
+            assert_eq!(assert_err!(parse(password)), PasswordPolicyError::TooWeak);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::password::hash_new_password;
+use crate::authentication::token::{generate_token, hash_token};
+use crate::authentication::{AuthError, NewPassword};
+
+/// How long a reset link stays valid after it has been emailed.
+pub const PASSWORD_RESET_TOKEN_TTL_MINUTES: i64 = 30;
+
+pub struct PasswordResetRecipient {
+    pub user_id: Uuid,
+    pub email: String,
+}
+
+#[tracing::instrument(name = "Get password reset recipient", skip(username, connection_pool))]
+pub async fn get_password_reset_recipient(
+    username: &str,
+    connection_pool: &PgPool,
+) -> Result<Option<PasswordResetRecipient>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT user_id, email FROM users WHERE username = $1"#,
+        username
+    )
+    .fetch_optional(connection_pool)
+    .await?
+    .and_then(|row| {
+        row.email.map(|email| PasswordResetRecipient {
+            user_id: row.user_id,
+            email,
+        })
+    });
+
+    Ok(row)
+}
+
+/// Creates a new reset token for the user and returns it in clear text.
+/// Only its hash is stored, so a leaked database does not hand out working reset links.
+#[tracing::instrument(name = "Create password reset token", skip(connection_pool))]
+pub async fn create_password_reset_token(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<Secret<String>, sqlx::Error> {
+    let token = generate_token(43);
+    let now = Utc::now();
+
+    sqlx::query!(
+        r#"INSERT INTO password_reset_tokens(token_hash, user_id, created_at, expires_at)
+        VALUES($1, $2, $3, $4)"#,
+        hash_token(&token),
+        user_id,
+        now,
+        now + Duration::minutes(PASSWORD_RESET_TOKEN_TTL_MINUTES)
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(token)
+}
+
+#[tracing::instrument(name = "Validate password reset token", skip_all)]
+pub async fn validate_password_reset_token(
+    token: &Secret<String>,
+    connection_pool: &PgPool,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT user_id FROM password_reset_tokens
+        WHERE token_hash = $1 AND expires_at > now()"#,
+        hash_token(token)
+    )
+    .fetch_optional(connection_pool)
+    .await?;
+
+    Ok(row.map(|row| row.user_id))
+}
+
+/// Sets a new password for the owner of a valid reset token.
+/// Every outstanding token for that user is invalidated, so a reset link works only once.
+/// Returns `None` if the token is unknown, expired or already used.
+#[tracing::instrument(name = "Reset password", skip_all)]
+pub async fn reset_password(
+    token: &Secret<String>,
+    password: NewPassword,
+    connection_pool: &PgPool,
+) -> Result<Option<Uuid>, AuthError> {
+    let password_hash = hash_new_password(password).await?;
+    let mut transaction = connection_pool.begin().await?;
+
+    let user_id = match sqlx::query!(
+        r#"DELETE FROM password_reset_tokens
+        WHERE token_hash = $1 AND expires_at > now()
+        RETURNING user_id"#,
+        hash_token(token)
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    {
+        Some(row) => row.user_id,
+        None => return Ok(None),
+    };
+
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"DELETE FROM password_reset_tokens WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(Some(user_id))
+}
@@ -0,0 +1,58 @@
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+/// A random alphanumeric secret, e.g. for reset links or API keys.
+pub(crate) fn generate_token(length: usize) -> Secret<String> {
+    let mut rng = thread_rng();
+    Secret::new(
+        std::iter::repeat_with(|| rng.sample(Alphanumeric))
+            .map(char::from)
+            .take(length)
+            .collect(),
+    )
+}
+
+/// Tokens are high-entropy, so a plain SHA-256 is enough to store them without making the
+/// database a source of working credentials; a slow password hash would only add latency.
+pub(crate) fn hash_token(token: &Secret<String>) -> String {
+    Sha256::digest(token.expose_secret().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Compares secrets without leaking, through timing, how long a prefix matched.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_token, hash_token};
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn generated_tokens_have_the_requested_length_and_are_distinct() {
+        let first = generate_token(43);
+        let second = generate_token(43);
+
+        assert_eq!(first.expose_secret().len(), 43);
+        assert!(first
+            .expose_secret()
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(first.expose_secret(), second.expose_secret());
+    }
+
+    #[test]
+    fn the_hash_is_stable_and_does_not_contain_the_token() {
+        let token = generate_token(43);
+        let hash = hash_token(&token);
+
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains(token.expose_secret().as_str()));
+        assert_eq!(hash, hash_token(&token));
+    }
+}
@@ -0,0 +1,202 @@
+//! Time-based one-time passwords (RFC 6238) with the parameters authenticator apps expect:
+//! HMAC-SHA1, 30 second steps and 6 digits.
+//!
+//! Every function takes the current time as an argument, so codes can be checked against a
+//! fixed clock.
+
+use chrono::{DateTime, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use sha1::Sha1;
+
+use crate::authentication::token::constant_time_eq;
+
+const TIME_STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+const SECRET_LENGTH_BYTES: usize = 20;
+// Accept the previous and the next code too, to absorb clock drift on the user's device.
+const ALLOWED_SKEW_STEPS: i64 = 1;
+const ISSUER: &str = "Newsletter";
+
+/// A new random secret, base32 encoded as authenticator apps expect it.
+pub fn generate_totp_secret() -> Secret<String> {
+    let mut secret = [0u8; SECRET_LENGTH_BYTES];
+    rand::thread_rng().fill_bytes(&mut secret);
+    Secret::new(BASE32_NOPAD.encode(&secret))
+}
+
+/// The `otpauth://` URI to show as a QR code when enrolling a device.
+pub fn provisioning_uri(secret: &Secret<String>, username: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={TIME_STEP_SECONDS}",
+        issuer = ISSUER,
+        username = percent_encode(username),
+        secret = secret.expose_secret(),
+    )
+}
+
+pub fn time_step(at: DateTime<Utc>) -> i64 {
+    at.timestamp().div_euclid(TIME_STEP_SECONDS)
+}
+
+/// The code for the step containing `at`, or `None` if the secret is not valid base32.
+pub fn totp_code(secret: &Secret<String>, at: DateTime<Utc>) -> Option<String> {
+    let key = decode_secret(secret)?;
+    Some(code_for_step(&key, time_step(at)))
+}
+
+/// Checks `code` against the steps around `now` and returns the step it matched.
+///
+/// Steps up to and including `last_used_step` are rejected, so a code cannot be replayed.
+pub fn verify_totp_code(
+    secret: &Secret<String>,
+    code: &str,
+    now: DateTime<Utc>,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let key = decode_secret(secret)?;
+    let current_step = time_step(now);
+    (current_step - ALLOWED_SKEW_STEPS..=current_step + ALLOWED_SKEW_STEPS)
+        .filter(|&step| last_used_step.is_none_or(|last_used| step > last_used))
+        .find(|&step| constant_time_eq(code_for_step(&key, step).as_bytes(), code.as_bytes()))
+}
+
+fn decode_secret(secret: &Secret<String>) -> Option<Vec<u8>> {
+    BASE32_NOPAD
+        .decode(secret.expose_secret().to_ascii_uppercase().as_bytes())
+        .ok()
+}
+
+fn code_for_step(key: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&(step as u64).to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation, RFC 4226 section 5.3.
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_totp_secret, provisioning_uri, time_step, totp_code, verify_totp_code};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use data_encoding::BASE32_NOPAD;
+    use secrecy::{ExposeSecret, Secret};
+
+    // The SHA1 secret used by the test vectors in RFC 6238, appendix B.
+    fn rfc_secret() -> Secret<String> {
+        Secret::new(BASE32_NOPAD.encode(b"12345678901234567890"))
+    }
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(timestamp, 0).unwrap()
+    }
+
+    #[test]
+    fn codes_match_the_rfc_6238_test_vectors() {
+        // The RFC lists 8 digit codes; 6 digit codes are their last 6 digits.
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        for (timestamp, expected) in vectors {
+            assert_eq!(totp_code(&rfc_secret(), at(timestamp)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn a_code_is_accepted_within_one_step_of_drift() {
+        let now = at(1234567890);
+        let code = totp_code(&rfc_secret(), now).unwrap();
+
+        for drift in [-30, 0, 30] {
+            let verified_at = now + Duration::seconds(drift);
+            assert_eq!(
+                verify_totp_code(&rfc_secret(), &code, verified_at, None),
+                Some(time_step(now))
+            );
+        }
+        for drift in [-60, 60] {
+            let verified_at = now + Duration::seconds(drift);
+            assert_eq!(
+                verify_totp_code(&rfc_secret(), &code, verified_at, None),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn a_code_cannot_be_replayed() {
+        let now = at(1234567890);
+        let code = totp_code(&rfc_secret(), now).unwrap();
+        let used_step = verify_totp_code(&rfc_secret(), &code, now, None).unwrap();
+
+        assert_eq!(
+            verify_totp_code(&rfc_secret(), &code, now, Some(used_step)),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_codes_are_rejected() {
+        let now = at(1234567890);
+        for code in ["", "12345", "1234567", "abcdef"] {
+            assert_eq!(verify_totp_code(&rfc_secret(), code, now, None), None);
+        }
+    }
+
+    #[test]
+    fn generated_secrets_decode_to_160_bits() {
+        let secret = generate_totp_secret();
+        let decoded = BASE32_NOPAD
+            .decode(secret.expose_secret().as_bytes())
+            .unwrap();
+
+        assert_eq!(decoded.len(), 20);
+    }
+
+    #[test]
+    fn the_provisioning_uri_names_the_issuer_and_account() {
+        let uri = provisioning_uri(&rfc_secret(), "ursula le guin");
+
+        assert!(uri.starts_with("otpauth://totp/Newsletter:ursula%20le%20guin?"));
+        assert!(uri.contains(&format!("secret={}", rfc_secret().expose_secret())));
+        assert!(uri.contains("issuer=Newsletter"));
+    }
+}
@@ -0,0 +1,247 @@
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::token::{generate_token, hash_token};
+use crate::authentication::totp::{generate_totp_secret, verify_totp_code};
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 12;
+
+pub enum TwoFactorStatus {
+    Disabled,
+    /// A secret has been generated but no code from it has been verified yet.
+    Pending(Secret<String>),
+    Enabled,
+}
+
+#[tracing::instrument(name = "Get two-factor status", skip(connection_pool))]
+pub async fn get_two_factor_status(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<TwoFactorStatus, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret, totp_confirmed_at FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(connection_pool)
+    .await?;
+
+    Ok(match (row.totp_secret, row.totp_confirmed_at) {
+        (Some(_), Some(_)) => TwoFactorStatus::Enabled,
+        (Some(secret), None) => TwoFactorStatus::Pending(Secret::new(secret)),
+        (None, _) => TwoFactorStatus::Disabled,
+    })
+}
+
+/// Generates a new secret for a user who has not enabled two-factor authentication yet.
+/// It only takes effect once [`confirm_totp_enrollment`] has seen a valid code from it.
+#[tracing::instrument(name = "Start TOTP enrollment", skip(connection_pool))]
+pub async fn start_totp_enrollment(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let secret = generate_totp_secret();
+    sqlx::query!(
+        r#"UPDATE users SET totp_secret = $1, totp_last_used_step = NULL
+        WHERE user_id = $2 AND totp_confirmed_at IS NULL"#,
+        secret.expose_secret(),
+        user_id
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Enables two-factor authentication if `code` matches the pending secret.
+/// Returns fresh recovery codes in clear text, or `None` if the code was wrong.
+#[tracing::instrument(name = "Confirm TOTP enrollment", skip(code, connection_pool))]
+pub async fn confirm_totp_enrollment(
+    user_id: Uuid,
+    code: &str,
+    now: DateTime<Utc>,
+    connection_pool: &PgPool,
+) -> Result<Option<Vec<Secret<String>>>, sqlx::Error> {
+    let secret = match get_two_factor_status(user_id, connection_pool).await? {
+        TwoFactorStatus::Pending(secret) => secret,
+        TwoFactorStatus::Disabled | TwoFactorStatus::Enabled => return Ok(None),
+    };
+    let step = match verify_totp_code(&secret, code, now, None) {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+
+    let mut transaction = connection_pool.begin().await?;
+    sqlx::query!(
+        r#"UPDATE users SET totp_confirmed_at = $1, totp_last_used_step = $2
+        WHERE user_id = $3"#,
+        now,
+        step,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut transaction)
+        .await?;
+    let recovery_codes: Vec<_> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_token(RECOVERY_CODE_LENGTH))
+        .collect();
+    let code_hashes: Vec<String> = recovery_codes.iter().map(hash_token).collect();
+    sqlx::query!(
+        r#"INSERT INTO recovery_codes(user_id, code_hash)
+        SELECT $1, code_hash FROM UNNEST($2::TEXT[]) AS code_hash"#,
+        user_id,
+        &code_hashes
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(Some(recovery_codes))
+}
+
+/// Checks the second login step. `code` is either the current TOTP code or an unused
+/// recovery code; both are single-use.
+#[tracing::instrument(name = "Verify second factor", skip(code, connection_pool))]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: &str,
+    now: DateTime<Utc>,
+    connection_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret, totp_last_used_step FROM users
+        WHERE user_id = $1 AND totp_confirmed_at IS NOT NULL"#,
+        user_id
+    )
+    .fetch_optional(connection_pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+
+    if let Some(secret) = row.totp_secret.map(Secret::new) {
+        if let Some(step) = verify_totp_code(&secret, code, now, row.totp_last_used_step) {
+            // Guard against two concurrent logins racing with the same code.
+            let updated = sqlx::query!(
+                r#"UPDATE users SET totp_last_used_step = $1
+                WHERE user_id = $2
+                    AND (totp_last_used_step IS NULL OR totp_last_used_step < $1)"#,
+                step,
+                user_id
+            )
+            .execute(connection_pool)
+            .await?
+            .rows_affected();
+            return Ok(updated > 0);
+        }
+    }
+
+    let used = sqlx::query!(
+        r#"UPDATE recovery_codes SET used_at = $1
+        WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL"#,
+        now,
+        user_id,
+        hash_token(&Secret::new(code.trim().to_string()))
+    )
+    .execute(connection_pool)
+    .await?
+    .rows_affected();
+
+    Ok(used > 0)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(connection_pool))]
+pub async fn disable_two_factor(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = connection_pool.begin().await?;
+    sqlx::query!(
+        r#"UPDATE users
+        SET totp_secret = NULL, totp_confirmed_at = NULL, totp_last_used_step = NULL
+        WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get two-factor policy", skip(connection_pool))]
+pub async fn is_two_factor_required(connection_pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(r#"SELECT require_two_factor FROM security_policy"#)
+        .fetch_one(connection_pool)
+        .await?;
+
+    Ok(row.require_two_factor)
+}
+
+#[tracing::instrument(name = "Set two-factor policy", skip(connection_pool))]
+pub async fn set_two_factor_required(
+    required: bool,
+    connection_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE security_policy SET require_two_factor = $1"#,
+        required
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Whether `user_id` has to present a second factor, because they enabled it or because the
+/// policy requires it. Such users cannot authenticate with a password alone.
+#[tracing::instrument(
+    name = "Check whether a second factor is expected",
+    skip(connection_pool)
+)]
+pub async fn is_second_factor_expected(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT security_policy.require_two_factor OR users.totp_confirmed_at IS NOT NULL
+            AS "expected!"
+        FROM users, security_policy
+        WHERE users.user_id = $1"#,
+        user_id
+    )
+    .fetch_one(connection_pool)
+    .await?;
+
+    Ok(row.expected)
+}
+
+/// Whether the policy requires two-factor authentication and `user_id` has not enabled it yet.
+#[tracing::instrument(
+    name = "Check whether two-factor enrollment is required",
+    skip(connection_pool)
+)]
+pub async fn is_enrollment_required(
+    user_id: Uuid,
+    connection_pool: &PgPool,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT security_policy.require_two_factor AND users.totp_confirmed_at IS NULL
+            AS "required!"
+        FROM users, security_policy
+        WHERE users.user_id = $1"#,
+        user_id
+    )
+    .fetch_one(connection_pool)
+    .await?;
+
+    Ok(row.required)
+}
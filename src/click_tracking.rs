@@ -0,0 +1,158 @@
+//! Rewrites the links of a newsletter issue so clicks go through `/t/c/{token}`, which records
+//! them before redirecting to the original URL.
+//!
+//! Only absolute `http(s)` links written by the author are rewritten: `href` attributes in the
+//! HTML body and bare URLs in the text body. Links to our own subscription pages, such as
+//! unsubscribe and confirmation links, are left alone so they keep working as is.
+
+use linkify::{LinkFinder, LinkKind};
+use secrecy::Secret;
+use uuid::Uuid;
+
+use crate::domain::{ClickToken, TrackedLink};
+
+pub struct ClickTracker<'a> {
+    base_url: &'a str,
+    hmac_secret: &'a Secret<String>,
+    subscriber_id: Uuid,
+    newsletter_issue_id: Uuid,
+}
+
+impl<'a> ClickTracker<'a> {
+    pub fn new(
+        base_url: &'a str,
+        hmac_secret: &'a Secret<String>,
+        subscriber_id: Uuid,
+        newsletter_issue_id: Uuid,
+    ) -> Self {
+        Self {
+            base_url,
+            hmac_secret,
+            subscriber_id,
+            newsletter_issue_id,
+        }
+    }
+
+    pub fn track_html(&self, html: &str) -> String {
+        rewrite_links(html, |before, url| {
+            if !(before.ends_with("href=\"") || before.ends_with("href='")) {
+                return None;
+            }
+            // The attribute value is HTML, the URL to redirect to is not.
+            self.tracking_link(&url.replace("&amp;", "&"))
+        })
+    }
+
+    pub fn track_text(&self, text: &str) -> String {
+        rewrite_links(text, |_, url| self.tracking_link(url))
+    }
+
+    fn tracking_link(&self, url: &str) -> Option<String> {
+        if !(url.starts_with("http://") || url.starts_with("https://"))
+            || url.starts_with(&format!("{}/subscriptions/", self.base_url))
+        {
+            return None;
+        }
+
+        let link = TrackedLink {
+            subscriber_id: self.subscriber_id,
+            newsletter_issue_id: self.newsletter_issue_id,
+            url: url.to_string(),
+        };
+        Some(format!(
+            "{}/t/c/{}",
+            self.base_url,
+            ClickToken::sign(&link, self.hmac_secret).as_ref()
+        ))
+    }
+}
+
+/// Replaces each URL found in `content` with what `rewrite` returns for it, given the content
+/// before the URL. URLs for which it returns `None` are kept.
+fn rewrite_links(content: &str, rewrite: impl Fn(&str, &str) -> Option<String>) -> String {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+
+    let mut out = String::with_capacity(content.len());
+    let mut copied = 0;
+    for link in finder.links(content) {
+        if let Some(replacement) = rewrite(&content[..link.start()], link.as_str()) {
+            out.push_str(&content[copied..link.start()]);
+            out.push_str(&replacement);
+            copied = link.end();
+        }
+    }
+    out.push_str(&content[copied..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClickTracker;
+    use crate::domain::ClickToken;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    const BASE_URL: &str = "https://news.example.com";
+
+    fn key() -> Secret<String> {
+        Secret::new("super-secret-signing-key".to_string())
+    }
+
+    /// The targets of the tracking links in `content`, in order.
+    fn tracked_urls(content: &str) -> Vec<String> {
+        let prefix = format!("{}/t/c/", BASE_URL);
+        content
+            .split(&prefix)
+            .skip(1)
+            .map(|rest| {
+                let token: String = rest
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+                    .collect();
+                ClickToken::verify(&token, &key()).unwrap().url
+            })
+            .collect()
+    }
+
+    #[test]
+    fn html_links_are_rewritten_but_not_their_labels_or_images() {
+        let key = key();
+        let tracker = ClickTracker::new(BASE_URL, &key, Uuid::new_v4(), Uuid::new_v4());
+
+        let html = tracker.track_html(
+            r#"<p><a href="https://example.com/a?x=1&amp;y=2">https://example.com/a</a>
+            <img src="https://example.com/logo.png"></p>"#,
+        );
+
+        assert_eq!(tracked_urls(&html), vec!["https://example.com/a?x=1&y=2"]);
+        assert!(html.contains(">https://example.com/a</a>"));
+        assert!(html.contains(r#"src="https://example.com/logo.png""#));
+    }
+
+    #[test]
+    fn text_links_are_rewritten() {
+        let key = key();
+        let tracker = ClickTracker::new(BASE_URL, &key, Uuid::new_v4(), Uuid::new_v4());
+
+        let text = tracker.track_text("Read https://example.com/a and https://example.com/b.");
+
+        assert_eq!(
+            tracked_urls(&text),
+            vec!["https://example.com/a", "https://example.com/b"]
+        );
+        assert!(text.starts_with("Read https://news.example.com/t/c/"));
+        assert!(text.ends_with('.'));
+    }
+
+    #[test]
+    fn subscription_links_and_email_addresses_are_not_rewritten() {
+        let key = key();
+        let tracker = ClickTracker::new(BASE_URL, &key, Uuid::new_v4(), Uuid::new_v4());
+        let text = "https://news.example.com/subscriptions/unsubscribe?token=x \
+            https://news.example.com/subscriptions/confirm?subscription_token=y \
+            editor@example.com";
+
+        assert_eq!(tracker.track_text(text), text);
+    }
+}
@@ -1,28 +1,204 @@
-use crate::domain::SubscriberEmail;
+use crate::domain::{SubscriberEmail, SubscriberEmailError};
+use crate::email_client::{
+    EmailSender, FileEmailClient, HttpEmailClient, PostmarkEmailClient, SmtpEmailClient,
+};
+use anyhow::Context;
 use config::ConfigError;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgConnectOptions;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The session cookie keys are derived from `hmac_secret`, which needs at least this many bytes.
+pub const MIN_HMAC_SECRET_LENGTH: usize = 64;
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application_port: u16,
     pub application_host_address: String,
+    pub application_base_url: String,
+    pub hmac_secret: Secret<String>,
+    pub session_store: SessionStoreKind,
 
     pub email_client_settings: EmailClientSettings,
+    pub email_template_settings: EmailTemplateSettings,
+    pub email_webhook_settings: EmailWebhookSettings,
+    pub issue_delivery_settings: IssueDeliverySettings,
+    pub tracking_settings: TrackingSettings,
+}
+
+#[derive(serde::Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStoreKind {
+    Postgres,
+    InMemory,
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
     pub sender: String,
-    pub base_url: String,
-    pub auth_token: Secret<String>,
+    pub connection: EmailConnectionSettings,
+    pub transport: EmailTransportSettings,
+}
+
+/// How the Postmark, HTTP and SMTP transports talk to their server.
+#[derive(serde::Deserialize, Clone)]
+pub struct EmailConnectionSettings {
+    /// For the whole request, from connecting to reading the response.
+    pub timeout_milliseconds: u64,
+    pub connect_timeout_milliseconds: u64,
+    /// Idle connections kept open to the email server, for reuse by later sends. For SMTP
+    /// this caps the whole connection pool.
+    pub max_idle_connections: usize,
+    /// Extra attempts after a failure that cannot have delivered the email, see
+    /// [`crate::email_client`]. Anything else is left to the caller to retry.
+    pub max_retries: u32,
+    /// Doubled after every retry.
+    pub retry_backoff_milliseconds: u64,
+}
+
+impl EmailConnectionSettings {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_millis(self.connect_timeout_milliseconds)
+    }
+
+    /// The delay before retry number `retry`, counting from 1.
+    pub fn retry_backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.retry_backoff_milliseconds)
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+    }
+}
+
+/// How outgoing email leaves the application, chosen by `kind`.
+#[derive(serde::Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EmailTransportSettings {
+    Postmark {
+        base_url: String,
+        auth_token: Secret<String>,
+    },
+    /// A provider-neutral JSON API, see [`HttpEmailClient`].
+    Http {
+        url: String,
+        auth_token: Secret<String>,
+    },
+    Smtp(SmtpSettings),
+    /// Writes `.eml` files to `directory` instead of sending anything.
+    File {
+        directory: String,
+    },
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+    #[serde(default = "default_starttls")]
+    pub starttls: bool,
+}
+
+fn default_starttls() -> bool {
+    true
 }
 
 impl EmailClientSettings {
-    pub fn sender_email(&self) -> Result<SubscriberEmail, String> {
+    pub fn sender_email(&self) -> Result<SubscriberEmail, SubscriberEmailError> {
         SubscriberEmail::parse(self.sender.clone())
     }
+
+    pub fn client(&self) -> Result<Arc<dyn EmailSender>, anyhow::Error> {
+        let sender_email = self
+            .sender_email()
+            .context("Invalid email sender address")?;
+        let client: Arc<dyn EmailSender> = match &self.transport {
+            EmailTransportSettings::Postmark {
+                base_url,
+                auth_token,
+            } => Arc::new(
+                PostmarkEmailClient::new(
+                    base_url.clone(),
+                    sender_email,
+                    auth_token.clone(),
+                    &self.connection,
+                )
+                .context("Failed to build the Postmark client")?,
+            ),
+            EmailTransportSettings::Http { url, auth_token } => Arc::new(
+                HttpEmailClient::new(
+                    url.clone(),
+                    sender_email,
+                    auth_token.clone(),
+                    &self.connection,
+                )
+                .context("Failed to build the HTTP email client")?,
+            ),
+            EmailTransportSettings::Smtp(smtp) => {
+                let credentials = smtp.username.clone().map(|username| {
+                    let password = smtp
+                        .password
+                        .clone()
+                        .unwrap_or_else(|| Secret::new(String::new()));
+                    (username, password)
+                });
+                Arc::new(
+                    SmtpEmailClient::new(
+                        &smtp.host,
+                        smtp.port,
+                        credentials,
+                        smtp.starttls,
+                        sender_email,
+                        &self.connection,
+                    )
+                    .context("Invalid SMTP host")?,
+                )
+            }
+            EmailTransportSettings::File { directory } => Arc::new(
+                FileEmailClient::new(directory, sender_email)
+                    .context("Failed to create the email directory")?,
+            ),
+        };
+
+        Ok(client)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct EmailTemplateSettings {
+    /// Where the email templates are read from, see [`crate::email_templates`].
+    pub directory: String,
+}
+
+/// The basic auth credentials the email provider is configured to send with its webhooks.
+#[derive(serde::Deserialize, Clone)]
+pub struct EmailWebhookSettings {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct IssueDeliverySettings {
+    pub max_retries: i32,
+    /// How many deliveries the worker takes from the queue, and sends, at once.
+    pub batch_size: i64,
+    pub base_backoff_seconds: u64,
+    pub max_backoff_seconds: u64,
+}
+
+/// Engagement tracking of newsletter issues. Either kind can be turned off for deployments
+/// that must not track their readers; links already sent then stop recording anything.
+#[derive(serde::Deserialize, Clone, Copy)]
+pub struct TrackingSettings {
+    /// Adds a tracking image to every newsletter email to record opens.
+    pub opens: bool,
+    /// Lets issues be published with `track_clicks`. When off, their links are left alone.
+    pub clicks: bool,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -42,7 +218,26 @@ pub fn get_configuration() -> Result<Settings, ConfigError> {
         ))
         .build()?;
 
-    settings.try_deserialize::<Settings>()
+    let settings = settings.try_deserialize::<Settings>()?;
+    settings.validate()?;
+
+    Ok(settings)
+}
+
+impl Settings {
+    /// Checks what deserializing cannot, so that a bad value fails here with a clear message
+    /// instead of somewhere during startup.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let hmac_secret_length = self.hmac_secret.expose_secret().len();
+        if hmac_secret_length < MIN_HMAC_SECRET_LENGTH {
+            return Err(ConfigError::Message(format!(
+                "hmac_secret must be at least {} bytes long, got {}",
+                MIN_HMAC_SECRET_LENGTH, hmac_secret_length
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 impl DatabaseSettings {
@@ -58,3 +253,27 @@ impl DatabaseSettings {
         self.without_db().database(&self.database_name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{get_configuration, MIN_HMAC_SECRET_LENGTH};
+    use secrecy::Secret;
+
+    #[test]
+    fn a_short_hmac_secret_is_rejected() {
+        let mut settings = get_configuration().expect("Failed to read the configuration");
+        settings.hmac_secret = Secret::new("a".repeat(MIN_HMAC_SECRET_LENGTH - 1));
+
+        let error = settings.validate().unwrap_err();
+
+        assert!(error.to_string().contains("hmac_secret"));
+    }
+
+    #[test]
+    fn a_long_enough_hmac_secret_is_accepted() {
+        let mut settings = get_configuration().expect("Failed to read the configuration");
+        settings.hmac_secret = Secret::new("a".repeat(MIN_HMAC_SECRET_LENGTH));
+
+        assert!(settings.validate().is_ok());
+    }
+}
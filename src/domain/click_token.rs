@@ -0,0 +1,108 @@
+use secrecy::Secret;
+use uuid::Uuid;
+
+use super::signed_token;
+
+const CONTEXT: &[u8] = b"click:";
+
+/// A click on a link of a newsletter issue, by one of its recipients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedLink {
+    pub subscriber_id: Uuid,
+    pub newsletter_issue_id: Uuid,
+    pub url: String,
+}
+
+/// A click tracking token: the subscriber id, the issue id and the target URL followed by a
+/// truncated HMAC-SHA256 of them, URL-safe base64 encoded. As the target is signed, the
+/// redirect cannot be pointed anywhere else.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClickToken(String);
+
+impl ClickToken {
+    pub fn sign(link: &TrackedLink, key: &Secret<String>) -> Self {
+        let mut payload = link.subscriber_id.as_bytes().to_vec();
+        payload.extend_from_slice(link.newsletter_issue_id.as_bytes());
+        payload.extend_from_slice(link.url.as_bytes());
+
+        Self(signed_token::sign(CONTEXT, &payload, key))
+    }
+
+    /// Returns the link if the token was signed with `key`.
+    pub fn verify(token: &str, key: &Secret<String>) -> Option<TrackedLink> {
+        let payload = signed_token::verify(CONTEXT, token, key)?;
+        if payload.len() < 32 {
+            return None;
+        }
+
+        let (subscriber_id, rest) = payload.split_at(16);
+        let (newsletter_issue_id, url) = rest.split_at(16);
+        Some(TrackedLink {
+            subscriber_id: Uuid::from_slice(subscriber_id).ok()?,
+            newsletter_issue_id: Uuid::from_slice(newsletter_issue_id).ok()?,
+            url: String::from_utf8(url.to_vec()).ok()?,
+        })
+    }
+}
+
+impl AsRef<str> for ClickToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClickToken, TrackedLink};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn key() -> Secret<String> {
+        Secret::new("super-secret-signing-key".to_string())
+    }
+
+    fn link() -> TrackedLink {
+        TrackedLink {
+            subscriber_id: Uuid::new_v4(),
+            newsletter_issue_id: Uuid::new_v4(),
+            url: "https://example.com/story?id=1".to_string(),
+        }
+    }
+
+    #[test]
+    fn a_signed_token_verifies_to_its_link() {
+        let link = link();
+        let token = ClickToken::sign(&link, &key());
+
+        assert_eq!(ClickToken::verify(token.as_ref(), &key()), Some(link));
+    }
+
+    #[test]
+    fn a_token_signed_with_another_key_is_rejected() {
+        let token = ClickToken::sign(&link(), &key());
+        let other_key = Secret::new("another-key".to_string());
+
+        assert_eq!(ClickToken::verify(token.as_ref(), &other_key), None);
+    }
+
+    #[test]
+    fn the_target_of_a_token_cannot_be_changed() {
+        let token = ClickToken::sign(&link(), &key());
+        let mut payload = URL_SAFE_NO_PAD.decode(token.as_ref()).unwrap();
+        payload[40] = b'X';
+
+        assert_eq!(
+            ClickToken::verify(&URL_SAFE_NO_PAD.encode(payload), &key()),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        for token in ["", "not base64!", "c2hvcnQ"] {
+            assert_eq!(ClickToken::verify(token, &key()), None);
+        }
+    }
+}
@@ -1,7 +1,16 @@
+mod click_token;
 mod new_subscriber;
+mod open_token;
+mod signed_token;
 mod subscriber_email;
 mod subscriber_name;
+mod subscription_status;
+mod unsubscribe_token;
 
+pub use click_token::{ClickToken, TrackedLink};
 pub use new_subscriber::NewSubscriber;
-pub use subscriber_email::SubscriberEmail;
-pub use subscriber_name::SubscriberName;
+pub use open_token::OpenToken;
+pub use subscriber_email::{SubscriberEmail, SubscriberEmailError};
+pub use subscriber_name::{SubscriberName, SubscriberNameError};
+pub use subscription_status::{IllegalTransition, SubscriptionStatus};
+pub use unsubscribe_token::UnsubscribeToken;
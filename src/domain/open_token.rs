@@ -0,0 +1,78 @@
+use secrecy::Secret;
+use uuid::Uuid;
+
+use super::signed_token;
+
+const CONTEXT: &[u8] = b"open:";
+
+/// An open tracking token, for the tracking image of one delivery: the subscriber id and the
+/// issue id followed by a truncated HMAC-SHA256 of them, URL-safe base64 encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenToken(String);
+
+impl OpenToken {
+    pub fn sign(subscriber_id: Uuid, newsletter_issue_id: Uuid, key: &Secret<String>) -> Self {
+        let mut payload = subscriber_id.as_bytes().to_vec();
+        payload.extend_from_slice(newsletter_issue_id.as_bytes());
+
+        Self(signed_token::sign(CONTEXT, &payload, key))
+    }
+
+    /// Returns the subscriber id and the issue id if the token was signed with `key`.
+    pub fn verify(token: &str, key: &Secret<String>) -> Option<(Uuid, Uuid)> {
+        let payload = signed_token::verify(CONTEXT, token, key)?;
+        if payload.len() != 32 {
+            return None;
+        }
+
+        let (subscriber_id, newsletter_issue_id) = payload.split_at(16);
+        Some((
+            Uuid::from_slice(subscriber_id).ok()?,
+            Uuid::from_slice(newsletter_issue_id).ok()?,
+        ))
+    }
+}
+
+impl AsRef<str> for OpenToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenToken;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn key() -> Secret<String> {
+        Secret::new("super-secret-signing-key".to_string())
+    }
+
+    #[test]
+    fn a_signed_token_verifies_to_its_delivery() {
+        let subscriber_id = Uuid::new_v4();
+        let newsletter_issue_id = Uuid::new_v4();
+        let token = OpenToken::sign(subscriber_id, newsletter_issue_id, &key());
+
+        assert_eq!(
+            OpenToken::verify(token.as_ref(), &key()),
+            Some((subscriber_id, newsletter_issue_id))
+        );
+    }
+
+    #[test]
+    fn a_token_signed_with_another_key_is_rejected() {
+        let token = OpenToken::sign(Uuid::new_v4(), Uuid::new_v4(), &key());
+        let other_key = Secret::new("another-key".to_string());
+
+        assert_eq!(OpenToken::verify(token.as_ref(), &other_key), None);
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        for token in ["", "not base64!", "c2hvcnQ"] {
+            assert_eq!(OpenToken::verify(token, &key()), None);
+        }
+    }
+}
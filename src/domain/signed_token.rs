@@ -0,0 +1,75 @@
+//! Tokens carrying their own payload, signed with the application key so that nothing needs to
+//! be stored to check them. Each kind of token signs its payload under its own `context`, e.g.
+//! `b"unsubscribe:"`, so that a token of one kind never verifies as another, or as anything else
+//! signed with the same key.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+const SIGNATURE_LENGTH: usize = 16;
+
+/// `payload` followed by a truncated HMAC-SHA256 of it, URL-safe base64 encoded.
+pub(super) fn sign(context: &[u8], payload: &[u8], key: &Secret<String>) -> String {
+    let signature = mac(context, payload, key).finalize().into_bytes();
+    let mut token = payload.to_vec();
+    token.extend_from_slice(&signature[..SIGNATURE_LENGTH]);
+
+    URL_SAFE_NO_PAD.encode(token)
+}
+
+/// Returns the payload of `token` if it was signed with `key` under `context`.
+pub(super) fn verify(context: &[u8], token: &str, key: &Secret<String>) -> Option<Vec<u8>> {
+    let token = URL_SAFE_NO_PAD.decode(token).ok()?;
+    if token.len() < SIGNATURE_LENGTH {
+        return None;
+    }
+
+    let (payload, signature) = token.split_at(token.len() - SIGNATURE_LENGTH);
+    mac(context, payload, key)
+        .verify_truncated_left(signature)
+        .ok()
+        .map(|_| payload.to_vec())
+}
+
+fn mac(context: &[u8], payload: &[u8], key: &Secret<String>) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(context);
+    mac.update(payload);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify};
+    use secrecy::Secret;
+
+    fn key() -> Secret<String> {
+        Secret::new("super-secret-signing-key".to_string())
+    }
+
+    #[test]
+    fn a_signed_token_verifies_to_its_payload() {
+        let token = sign(b"test:", b"payload", &key());
+
+        assert_eq!(verify(b"test:", &token, &key()), Some(b"payload".to_vec()));
+    }
+
+    #[test]
+    fn a_token_signed_under_another_context_is_rejected() {
+        let token = sign(b"test:", b"payload", &key());
+
+        assert_eq!(verify(b"other:", &token, &key()), None);
+    }
+
+    #[test]
+    fn a_token_signed_with_another_key_is_rejected() {
+        let token = sign(b"test:", b"payload", &key());
+        let other_key = Secret::new("another-key".to_string());
+
+        assert_eq!(verify(b"test:", &token, &other_key), None);
+    }
+}
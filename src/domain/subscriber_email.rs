@@ -3,13 +3,17 @@ use validator::validate_email;
 #[derive(Debug)]
 pub struct SubscriberEmail(String);
 
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("email is not a valid email address")]
+pub struct SubscriberEmailError;
+
 impl SubscriberEmail {
-    pub fn parse(email: String) -> Result<SubscriberEmail, String> {
+    pub fn parse(email: String) -> Result<SubscriberEmail, SubscriberEmailError> {
         if validate_email(&email) {
             return Ok(Self(email));
         }
 
-        Err("Invalid email".to_string())
+        Err(SubscriberEmailError)
     }
 }
 
@@ -21,7 +25,7 @@ impl AsRef<str> for SubscriberEmail {
 
 #[cfg(test)]
 mod tests {
-    use super::SubscriberEmail;
+    use super::{SubscriberEmail, SubscriberEmailError};
     use claims::{assert_err_eq, assert_ok};
     use fake::{faker::internet::en::SafeEmail, Fake};
 
@@ -43,6 +47,6 @@ mod tests {
 
     #[quickcheck_macros::quickcheck]
     fn subscriber_email_is_parsing_failed(email: String) {
-        assert_err_eq!(SubscriberEmail::parse(email), "Invalid email".to_string());
+        assert_err_eq!(SubscriberEmail::parse(email), SubscriberEmailError);
     }
 }
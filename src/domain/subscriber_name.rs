@@ -2,15 +2,30 @@ use unicode_segmentation::UnicodeSegmentation;
 #[derive(Debug)]
 pub struct SubscriberName(String);
 
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum SubscriberNameError {
+    #[error("name must not be empty")]
+    Empty,
+    #[error("name must not be longer than 256 characters")]
+    TooLong,
+    #[error("name must not contain any of / ( ) \" < > \\ {{ }}")]
+    ForbiddenCharacters,
+}
+
 impl SubscriberName {
-    pub(crate) fn parse(name: String) -> Result<SubscriberName, String> {
-        let is_not_empty = name.trim().is_empty();
-        let is_too_long = name.graphemes(true).count() > 256;
+    pub(crate) fn parse(name: String) -> Result<SubscriberName, SubscriberNameError> {
         let special_char = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
-        let has_special_char = name.chars().any(|c| special_char.contains(&c));
 
-        if is_not_empty || is_too_long || has_special_char {
-            return Err(format!("Invalid name: {}", name));
+        if name.trim().is_empty() {
+            return Err(SubscriberNameError::Empty);
+        }
+
+        if name.graphemes(true).count() > 256 {
+            return Err(SubscriberNameError::TooLong);
+        }
+
+        if name.chars().any(|c| special_char.contains(&c)) {
+            return Err(SubscriberNameError::ForbiddenCharacters);
         }
 
         Ok(SubscriberName(name.to_string()))
@@ -25,9 +40,9 @@ impl AsRef<str> for SubscriberName {
 
 #[cfg(test)]
 mod tests {
-    use super::SubscriberName;
+    use super::{SubscriberName, SubscriberNameError};
 
-    use claims::assert_err;
+    use claims::assert_err_eq;
     use claims::assert_ok;
 
     #[test]
@@ -45,17 +60,26 @@ mod tests {
     #[test]
     fn a_long_name_more_than_256_char_is_invalid() {
         let name = "a".repeat(257);
-        assert_err!(SubscriberName::parse(name));
+        assert_err_eq!(SubscriberName::parse(name), SubscriberNameError::TooLong);
     }
 
     #[test]
     fn a_name_containing_invalid_char_is_invalid() {
         for name in &['/', '(', ')', '"', '<', '>', '\\', '{', '}'] {
             let name = name.to_string();
-            assert_err!(SubscriberName::parse(name));
+            assert_err_eq!(
+                SubscriberName::parse(name),
+                SubscriberNameError::ForbiddenCharacters
+            );
         }
     }
 
+    #[test]
+    fn a_whitespace_only_name_is_invalid() {
+        let name = " ".repeat(10);
+        assert_err_eq!(SubscriberName::parse(name), SubscriberNameError::Empty);
+    }
+
     #[test]
     fn a_valid_name_is_parse() {
         let name = "a valid name".to_string();
@@ -0,0 +1,170 @@
+/// Where a subscriber is in their lifecycle. Stored as text in `subscriptions.status`.
+///
+/// Moves between states go through [`SubscriptionStatus::transition_to`], which encodes
+/// the allowed transitions:
+///
+/// - a pending subscriber can confirm, unsubscribe, bounce, complain or be suppressed;
+/// - a confirmed subscriber can unsubscribe, bounce, complain or be suppressed;
+/// - bounces, complaints and suppressions still apply after unsubscribing;
+/// - unsubscribed, bounced and suppressed addresses can only come back by signing up again,
+///   which puts them back to pending until they confirm;
+/// - complaints can only be escalated to a suppression.
+///
+/// The suppression list, not the status, decides whether an address may receive email, so a
+/// suppressed address that signs up again stays silent until an admin lifts the suppression.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SubscriptionStatus {
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+    Bounced,
+    Complained,
+    Suppressed,
+}
+
+impl SubscriptionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubscriptionStatus::PendingConfirmation => "pending-confirmation",
+            SubscriptionStatus::Confirmed => "confirmed",
+            SubscriptionStatus::Unsubscribed => "unsubscribed",
+            SubscriptionStatus::Bounced => "bounced",
+            SubscriptionStatus::Complained => "complained",
+            SubscriptionStatus::Suppressed => "suppressed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        [
+            SubscriptionStatus::PendingConfirmation,
+            SubscriptionStatus::Confirmed,
+            SubscriptionStatus::Unsubscribed,
+            SubscriptionStatus::Bounced,
+            SubscriptionStatus::Complained,
+            SubscriptionStatus::Suppressed,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == status)
+    }
+
+    pub fn can_transition_to(self, next: SubscriptionStatus) -> bool {
+        use SubscriptionStatus::*;
+
+        matches!(
+            (self, next),
+            (PendingConfirmation, Confirmed | Unsubscribed)
+                | (Confirmed, Unsubscribed)
+                | (Unsubscribed | Bounced | Suppressed, PendingConfirmation)
+                | (Bounced, Unsubscribed)
+                | (PendingConfirmation | Confirmed | Unsubscribed, Bounced)
+                | (
+                    PendingConfirmation | Confirmed | Unsubscribed | Bounced,
+                    Complained
+                )
+                | (
+                    PendingConfirmation | Confirmed | Unsubscribed | Bounced | Complained,
+                    Suppressed
+                )
+        )
+    }
+
+    /// Returns `next` if the move is allowed. Staying in the same state is not a transition.
+    pub fn transition_to(
+        self,
+        next: SubscriptionStatus,
+    ) -> Result<SubscriptionStatus, IllegalTransition> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(IllegalTransition {
+                from: self,
+                to: next,
+            })
+        }
+    }
+}
+
+impl std::fmt::Display for SubscriptionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("a subscriber cannot move from `{from}` to `{to}`")]
+pub struct IllegalTransition {
+    pub from: SubscriptionStatus,
+    pub to: SubscriptionStatus,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionStatus::{self, *};
+
+    const ALL: [SubscriptionStatus; 6] = [
+        PendingConfirmation,
+        Confirmed,
+        Unsubscribed,
+        Bounced,
+        Complained,
+        Suppressed,
+    ];
+
+    #[test]
+    fn statuses_round_trip_through_their_string_form() {
+        for status in ALL {
+            assert_eq!(SubscriptionStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(SubscriptionStatus::parse("deleted"), None);
+    }
+
+    #[test]
+    fn confirming_requires_a_pending_subscription() {
+        for status in ALL {
+            assert_eq!(
+                status.can_transition_to(Confirmed),
+                status == PendingConfirmation,
+                "{} -> confirmed",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn unsubscribed_bounced_and_suppressed_addresses_come_back_through_a_new_sign_up() {
+        for status in [Unsubscribed, Bounced, Suppressed] {
+            assert!(status.transition_to(PendingConfirmation).is_ok());
+            assert!(status.transition_to(Confirmed).is_err());
+        }
+        assert!(Confirmed.transition_to(PendingConfirmation).is_err());
+    }
+
+    #[test]
+    fn complaints_can_only_be_escalated_to_a_suppression() {
+        for status in ALL {
+            assert_eq!(
+                Complained.can_transition_to(status),
+                status == Suppressed,
+                "complained -> {}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn staying_in_the_same_state_is_not_a_transition() {
+        for status in ALL {
+            assert!(status.transition_to(status).is_err());
+        }
+    }
+
+    #[test]
+    fn illegal_transitions_name_both_states() {
+        let error = Unsubscribed.transition_to(Confirmed).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "a subscriber cannot move from `unsubscribed` to `confirmed`"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use secrecy::Secret;
+use uuid::Uuid;
+
+use super::signed_token;
+
+const CONTEXT: &[u8] = b"unsubscribe:";
+
+/// A per-subscriber unsubscribe token: the subscriber id followed by a truncated
+/// HMAC-SHA256 of it, URL-safe base64 encoded. Nothing needs to be stored to check it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsubscribeToken(String);
+
+impl UnsubscribeToken {
+    pub fn sign(subscriber_id: Uuid, key: &Secret<String>) -> Self {
+        Self(signed_token::sign(CONTEXT, subscriber_id.as_bytes(), key))
+    }
+
+    /// Returns the subscriber id if the token was signed with `key`.
+    pub fn verify(token: &str, key: &Secret<String>) -> Option<Uuid> {
+        let payload = signed_token::verify(CONTEXT, token, key)?;
+        Uuid::from_slice(&payload).ok()
+    }
+}
+
+impl AsRef<str> for UnsubscribeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnsubscribeToken;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn key() -> Secret<String> {
+        Secret::new("super-secret-signing-key".to_string())
+    }
+
+    #[test]
+    fn a_signed_token_verifies_to_its_subscriber() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::sign(subscriber_id, &key());
+
+        assert_eq!(
+            UnsubscribeToken::verify(token.as_ref(), &key()),
+            Some(subscriber_id)
+        );
+    }
+
+    #[test]
+    fn a_token_signed_with_another_key_is_rejected() {
+        let token = UnsubscribeToken::sign(Uuid::new_v4(), &key());
+        let other_key = Secret::new("another-key".to_string());
+
+        assert_eq!(UnsubscribeToken::verify(token.as_ref(), &other_key), None);
+    }
+
+    #[test]
+    fn a_token_for_another_subscriber_cannot_be_forged() {
+        let token = UnsubscribeToken::sign(Uuid::new_v4(), &key());
+        let mut payload = URL_SAFE_NO_PAD.decode(token.as_ref()).unwrap();
+        payload[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+
+        assert_eq!(
+            UnsubscribeToken::verify(&URL_SAFE_NO_PAD.encode(payload), &key()),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        for token in ["", "not base64!", "c2hvcnQ"] {
+            assert_eq!(UnsubscribeToken::verify(token, &key()), None);
+        }
+    }
+}
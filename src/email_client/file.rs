@@ -0,0 +1,86 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::{build_message, EmailHeader, EmailSender, SendEmailError};
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+use std::path::PathBuf;
+
+/// Writes every email to `<directory>/<id>.eml` instead of sending it. Meant for local
+/// development, where the files can be opened in any mail client.
+pub struct FileEmailClient {
+    transport: AsyncFileTransport<Tokio1Executor>,
+    sender: SubscriberEmail,
+}
+
+impl FileEmailClient {
+    /// Creates `directory` if it does not exist yet.
+    pub fn new(directory: impl Into<PathBuf>, sender: SubscriberEmail) -> std::io::Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            transport: AsyncFileTransport::new(directory),
+            sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for FileEmailClient {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader<'_>],
+    ) -> Result<(), SendEmailError> {
+        let message = build_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| SendEmailError::Unavailable(e.into()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailSender, FileEmailClient};
+
+    #[tokio::test]
+    async fn each_email_is_written_to_an_eml_file() {
+        let directory = std::env::temp_dir().join(format!("emails-{}", uuid::Uuid::new_v4()));
+        let client = FileEmailClient::new(
+            &directory,
+            SubscriberEmail::parse("sender@example.com".into()).unwrap(),
+        )
+        .unwrap();
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        client
+            .send_email(&recipient, "Hello", "<p>Hi</p>", "Hi")
+            .await
+            .unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(contents.contains("To: ursula@example.com"));
+        assert!(contents.contains("Subject: Hello"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+use crate::configuration::EmailConnectionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::{
+    build_http_client, http_error_is_safe_to_retry, send_with_retries, EmailHeader, EmailSender,
+    SendEmailError,
+};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+
+/// Posts a provider-neutral JSON body to `url`, authenticated with a bearer token. For
+/// providers, or in-house relays, that accept this shape or sit behind an adapter.
+pub struct HttpEmailClient {
+    http_client: Client,
+    url: String,
+    sender: SubscriberEmail,
+    auth_token: Secret<String>,
+    settings: EmailConnectionSettings,
+}
+
+impl HttpEmailClient {
+    pub fn new(
+        url: String,
+        sender: SubscriberEmail,
+        auth_token: Secret<String>,
+        settings: &EmailConnectionSettings,
+    ) -> Result<Self, reqwest::Error> {
+        Ok(Self {
+            http_client: build_http_client(settings)?,
+            url,
+            sender,
+            auth_token,
+            settings: settings.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for HttpEmailClient {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader<'_>],
+    ) -> Result<(), SendEmailError> {
+        let send_email_request = SendEmailRequest {
+            from: self.sender.as_ref(),
+            to: recipient.as_ref(),
+            subject,
+            html: html_content,
+            text: text_content,
+            headers: headers
+                .iter()
+                .map(|header| HttpEmailHeader {
+                    name: header.name,
+                    value: header.value,
+                })
+                .collect(),
+        };
+
+        send_with_retries(&self.settings, http_error_is_safe_to_retry, || async {
+            self.http_client
+                .post(&self.url)
+                .json(&send_email_request)
+                .bearer_auth(self.auth_token.expose_secret())
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+        .await
+        .map_err(SendEmailError::from_http_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html: &'a str,
+    text: &'a str,
+    headers: Vec<HttpEmailHeader<'a>>,
+}
+
+#[derive(Serialize)]
+struct HttpEmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::EmailConnectionSettings;
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailHeader, EmailSender, HttpEmailClient, SendEmailError};
+    use claims::assert_ok;
+    use secrecy::Secret;
+    use wiremock::matchers::{any, body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn client(server: &MockServer) -> HttpEmailClient {
+        HttpEmailClient::new(
+            format!("{}/send", server.uri()),
+            SubscriberEmail::parse("sender@example.com".into()).unwrap(),
+            Secret::new("token".into()),
+            &EmailConnectionSettings {
+                timeout_milliseconds: 1000,
+                connect_timeout_milliseconds: 1000,
+                max_idle_connections: 1,
+                max_retries: 0,
+                retry_backoff_milliseconds: 10,
+            },
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn posts_a_json_body_with_a_bearer_token() {
+        // Arrange
+        let server = MockServer::start().await;
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        Mock::given(method("POST"))
+            .and(path("/send"))
+            .and(header("Authorization", "Bearer token"))
+            .and(body_json(serde_json::json!({
+                "from": "sender@example.com",
+                "to": "ursula@example.com",
+                "subject": "Hello",
+                "html": "<p>Hi</p>",
+                "text": "Hi",
+                "headers": [{ "name": "List-Unsubscribe", "value": "<https://example.com/u>" }]
+            })))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = client(&server)
+            .send_email_with_headers(
+                &recipient,
+                "Hello",
+                "<p>Hi</p>",
+                "Hi",
+                &[EmailHeader {
+                    name: "List-Unsubscribe",
+                    value: "<https://example.com/u>",
+                }],
+            )
+            .await;
+
+        // Assert
+        assert_ok!(response);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_are_not_rejections() {
+        // Arrange
+        let server = MockServer::start().await;
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = client(&server)
+            .send_email(&recipient, "Hello", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert!(matches!(
+            response.unwrap_err(),
+            SendEmailError::Unavailable(_)
+        ));
+    }
+}
@@ -0,0 +1,54 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::{EmailHeader, EmailSender, SendEmailError};
+use std::sync::Mutex;
+
+/// Records emails instead of sending them, so tests can assert on what would have gone out.
+#[derive(Default)]
+pub struct InMemoryEmailClient {
+    sent_emails: Mutex<Vec<SentEmail>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl InMemoryEmailClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every email sent so far, oldest first.
+    pub fn sent_emails(&self) -> Vec<SentEmail> {
+        self.sent_emails.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for InMemoryEmailClient {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader<'_>],
+    ) -> Result<(), SendEmailError> {
+        self.sent_emails.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().to_string(),
+            subject: subject.to_string(),
+            html_content: html_content.to_string(),
+            text_content: text_content.to_string(),
+            headers: headers
+                .iter()
+                .map(|header| (header.name.to_string(), header.value.to_string()))
+                .collect(),
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Outgoing email. Handlers and the delivery worker only see the [`EmailSender`] trait;
+//! `EmailClientSettings::client` picks the transport.
+//!
+//! Sending an email is not idempotent: after a timeout or a 500 we cannot tell whether the
+//! provider accepted it. Transports only retry on their own when the email certainly was not
+//! accepted, e.g. the connection was never established or the server asked us to come back
+//! later, and leave every other failure to the caller.
+
+mod file;
+mod http;
+mod in_memory;
+mod postmark;
+mod smtp;
+
+pub use file::FileEmailClient;
+pub use http::HttpEmailClient;
+pub use in_memory::{InMemoryEmailClient, SentEmail};
+pub use postmark::PostmarkEmailClient;
+pub use smtp::SmtpEmailClient;
+
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+use serde::Serialize;
+use std::future::Future;
+
+use crate::configuration::EmailConnectionSettings;
+use crate::domain::SubscriberEmail;
+use crate::routes::error_chain_fmt;
+
+#[async_trait::async_trait]
+pub trait EmailSender: Send + Sync {
+    /// Sends one email, with extra headers on the outgoing message, e.g. `List-Unsubscribe`.
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader<'_>],
+    ) -> Result<(), SendEmailError>;
+
+    async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), SendEmailError> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+
+    /// Sends many emails, returning one result per email in the same order, so that callers
+    /// can handle each failure on its own. Transports whose provider accepts batches override
+    /// this to send many emails per request; the default sends them one at a time.
+    async fn send_batch(&self, emails: &[OutgoingEmail<'_>]) -> Vec<Result<(), SendEmailError>> {
+        let mut results = Vec::with_capacity(emails.len());
+        for email in emails {
+            results.push(
+                self.send_email_with_headers(
+                    email.recipient,
+                    email.subject,
+                    email.html_content,
+                    email.text_content,
+                    email.headers,
+                )
+                .await,
+            );
+        }
+        results
+    }
+}
+
+/// One email of a batch, see [`EmailSender::send_batch`].
+#[derive(Clone, Copy)]
+pub struct OutgoingEmail<'a> {
+    pub recipient: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a [EmailHeader<'a>],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmailHeader<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+/// Whether a failed send is worth retrying is the transport's call, since only it knows
+/// what its errors mean.
+#[derive(thiserror::Error)]
+pub enum SendEmailError {
+    /// The message or recipient was refused; sending it again will fail the same way.
+    #[error("the email was rejected: {0:#}")]
+    Rejected(anyhow::Error),
+    /// Timeouts, connection failures, rate limits and server-side errors.
+    #[error("the email could not be sent: {0:#}")]
+    Unavailable(anyhow::Error),
+    /// The provider took the email but did not tell us what became of it. It may well have
+    /// been sent, so it must not be sent again.
+    #[error("the outcome of the email is unknown: {0:#}")]
+    Unconfirmed(anyhow::Error),
+}
+
+impl std::fmt::Debug for SendEmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl SendEmailError {
+    /// The same error for another email, when a whole batch failed at once.
+    fn for_another_email(&self) -> Self {
+        match self {
+            SendEmailError::Rejected(e) => SendEmailError::Rejected(anyhow::anyhow!("{:#}", e)),
+            SendEmailError::Unavailable(e) => {
+                SendEmailError::Unavailable(anyhow::anyhow!("{:#}", e))
+            }
+            SendEmailError::Unconfirmed(e) => {
+                SendEmailError::Unconfirmed(anyhow::anyhow!("{:#}", e))
+            }
+        }
+    }
+
+    /// Classifies an error from an HTTP API: 4xx responses other than 429 are rejections.
+    fn from_http_error(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status)
+                if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS =>
+            {
+                SendEmailError::Rejected(error.into())
+            }
+            _ => SendEmailError::Unavailable(error.into()),
+        }
+    }
+}
+
+/// The HTTP client for the transports that call an API, with the timeouts and connection
+/// pool from `settings`.
+fn build_http_client(
+    settings: &EmailConnectionSettings,
+) -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .timeout(settings.timeout())
+        .connect_timeout(settings.connect_timeout())
+        .pool_max_idle_per_host(settings.max_idle_connections)
+        .build()
+}
+
+/// Whether a failed API call certainly did not hand the email over to the provider.
+fn http_error_is_safe_to_retry(error: &reqwest::Error) -> bool {
+    error.is_connect()
+        || matches!(
+            error.status(),
+            Some(reqwest::StatusCode::TOO_MANY_REQUESTS | reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        )
+}
+
+/// Calls `send` until it succeeds, fails in a way `is_safe_to_retry` rejects, or runs out of
+/// the retries allowed by `settings`.
+async fn send_with_retries<T, E, F, Fut>(
+    settings: &EmailConnectionSettings,
+    is_safe_to_retry: impl Fn(&E) -> bool,
+    mut send: F,
+) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut retry = 0;
+    loop {
+        match send().await {
+            Err(e) if retry < settings.max_retries && is_safe_to_retry(&e) => {
+                retry += 1;
+                let delay = settings.retry_backoff(retry);
+                tracing::warn!(
+                    error.message = %e,
+                    retry,
+                    delay_milliseconds = delay.as_millis() as u64,
+                    "The email was not accepted. Retrying",
+                );
+                tokio::time::sleep(delay).await;
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+/// Builds the MIME message for transports that speak raw email rather than a JSON API.
+fn build_message(
+    sender: &SubscriberEmail,
+    recipient: &SubscriberEmail,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    headers: &[EmailHeader<'_>],
+) -> Result<Message, SendEmailError> {
+    let mailbox = |email: &SubscriberEmail| {
+        email
+            .as_ref()
+            .parse::<Mailbox>()
+            .map_err(|e| SendEmailError::Rejected(e.into()))
+    };
+
+    let mut message = Message::builder()
+        .from(mailbox(sender)?)
+        .to(mailbox(recipient)?)
+        .subject(subject)
+        .multipart(MultiPart::alternative_plain_html(
+            text_content.to_string(),
+            html_content.to_string(),
+        ))
+        .map_err(|e| SendEmailError::Rejected(e.into()))?;
+    for header in headers {
+        let name = HeaderName::new_from_ascii(header.name.to_string())
+            .map_err(|e| SendEmailError::Rejected(e.into()))?;
+        message
+            .headers_mut()
+            .insert_raw(HeaderValue::new(name, header.value.to_string()));
+    }
+
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_message, EmailHeader};
+    use crate::domain::SubscriberEmail;
+
+    #[test]
+    fn built_messages_carry_both_bodies_and_custom_headers() {
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let message = build_message(
+            &sender,
+            &recipient,
+            "Hello",
+            "<p>Hi there</p>",
+            "Hi there",
+            &[EmailHeader {
+                name: "List-Unsubscribe",
+                value: "<https://example.com/u>",
+            }],
+        )
+        .unwrap();
+
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("From: sender@example.com"));
+        assert!(formatted.contains("To: ursula@example.com"));
+        assert!(formatted.contains("Subject: Hello"));
+        assert!(formatted.contains("List-Unsubscribe: <https://example.com/u>"));
+        assert!(formatted.contains("Content-Type: text/plain"));
+        assert!(formatted.contains("<p>Hi there</p>"));
+    }
+}
@@ -0,0 +1,682 @@
+use crate::configuration::EmailConnectionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::{
+    build_http_client, http_error_is_safe_to_retry, send_with_retries, EmailHeader, EmailSender,
+    OutgoingEmail, SendEmailError,
+};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+/// The most messages Postmark accepts in one `/email/batch` request.
+const MAX_BATCH_SIZE: usize = 500;
+
+/// Sends through Postmark's `/email` and `/email/batch` APIs.
+pub struct PostmarkEmailClient {
+    http_client: Client,
+    base_url: String,
+    sender: SubscriberEmail,
+    auth_token: Secret<String>,
+    settings: EmailConnectionSettings,
+}
+
+impl PostmarkEmailClient {
+    /// Fails if the HTTP client cannot be built, e.g. when no TLS backend can be initialised.
+    pub fn new(
+        base_url: String,
+        sender: SubscriberEmail,
+        auth_token: Secret<String>,
+        settings: &EmailConnectionSettings,
+    ) -> Result<Self, reqwest::Error> {
+        Ok(Self {
+            http_client: build_http_client(settings)?,
+            base_url,
+            sender,
+            auth_token,
+            settings: settings.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for PostmarkEmailClient {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader<'_>],
+    ) -> Result<(), SendEmailError> {
+        let url = format!("{}/email", self.base_url);
+        let send_email_request = SendEmailRequest {
+            from: self.sender.as_ref(),
+            to: recipient.as_ref(),
+            subject,
+            html_content,
+            text_content,
+            headers,
+        };
+
+        send_with_retries(&self.settings, http_error_is_safe_to_retry, || async {
+            self.http_client
+                .post(&url)
+                .json(&send_email_request)
+                .header("X-Postmark-Server-Token", self.auth_token.expose_secret())
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+        .await
+        .map_err(SendEmailError::from_http_error)?;
+
+        Ok(())
+    }
+
+    async fn send_batch(&self, emails: &[OutgoingEmail<'_>]) -> Vec<Result<(), SendEmailError>> {
+        let mut results = Vec::with_capacity(emails.len());
+        for chunk in emails.chunks(MAX_BATCH_SIZE) {
+            if let [email] = chunk {
+                // A batch of one goes through `/email`, whose errors carry an HTTP status.
+                results.push(
+                    self.send_email_with_headers(
+                        email.recipient,
+                        email.subject,
+                        email.html_content,
+                        email.text_content,
+                        email.headers,
+                    )
+                    .await,
+                );
+            } else {
+                results.extend(self.send_chunk(chunk).await);
+            }
+        }
+        results
+    }
+}
+
+impl PostmarkEmailClient {
+    async fn send_chunk(&self, emails: &[OutgoingEmail<'_>]) -> Vec<Result<(), SendEmailError>> {
+        let url = format!("{}/email/batch", self.base_url);
+        let send_email_requests: Vec<_> = emails
+            .iter()
+            .map(|email| SendEmailRequest {
+                from: self.sender.as_ref(),
+                to: email.recipient.as_ref(),
+                subject: email.subject,
+                html_content: email.html_content,
+                text_content: email.text_content,
+                headers: email.headers,
+            })
+            .collect();
+
+        let response = send_with_retries(&self.settings, http_error_is_safe_to_retry, || async {
+            self.http_client
+                .post(&url)
+                .json(&send_email_requests)
+                .header("X-Postmark-Server-Token", self.auth_token.expose_secret())
+                .send()
+                .await?
+                .error_for_status()
+        })
+        .await
+        .map_err(SendEmailError::from_http_error);
+        let response = match response {
+            Ok(response) => response,
+            Err(error) => {
+                return emails
+                    .iter()
+                    .map(|_| Err(error.for_another_email()))
+                    .collect()
+            }
+        };
+
+        // Postmark has taken the batch, so from here on no message may be reported as safe to
+        // send again: those without a readable result are unconfirmed.
+        let (results, problem) = match response.json::<Vec<serde_json::Value>>().await {
+            Ok(results) if results.len() == emails.len() => (results, String::new()),
+            Ok(results) => {
+                let problem = format!(
+                    "Postmark answered a batch of {} emails with {} results",
+                    emails.len(),
+                    results.len()
+                );
+                (results, problem)
+            }
+            Err(e) => (
+                Vec::new(),
+                format!("the response of Postmark could not be decoded: {}", e),
+            ),
+        };
+        let mut results = results.into_iter();
+
+        emails
+            .iter()
+            .map(|_| match results.next() {
+                Some(result) => serde_json::from_value::<BatchResponse>(result)
+                    .map_err(|e| {
+                        SendEmailError::Unconfirmed(anyhow::anyhow!(
+                            "the result of Postmark for this email could not be decoded: {}",
+                            e
+                        ))
+                    })
+                    .and_then(BatchResponse::into_result),
+                None => Err(SendEmailError::Unconfirmed(anyhow::anyhow!("{}", problem))),
+            })
+            .collect()
+    }
+}
+
+/// The outcome of one message of a batch. Postmark answers `200` for the whole batch and
+/// reports a non-zero `ErrorCode` for each message it refused.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BatchResponse {
+    error_code: i64,
+    message: String,
+}
+
+impl BatchResponse {
+    fn into_result(self) -> Result<(), SendEmailError> {
+        if self.error_code == 0 {
+            Ok(())
+        } else {
+            Err(SendEmailError::Rejected(anyhow::anyhow!(
+                "Postmark error {}: {}",
+                self.error_code,
+                self.message
+            )))
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_content: &'a str,
+    text_content: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader<'a>],
+}
+
+#[cfg(test)]
+mod email_client_tests {
+    use crate::configuration::EmailConnectionSettings;
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        EmailHeader, EmailSender, OutgoingEmail, PostmarkEmailClient, SendEmailError,
+    };
+    use claims::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, body_partial_json, header, header_exists, method, path};
+    use wiremock::{Match, Mock, MockServer, Request, ResponseTemplate};
+
+    fn connection_settings() -> EmailConnectionSettings {
+        EmailConnectionSettings {
+            timeout_milliseconds: 1000,
+            connect_timeout_milliseconds: 1000,
+            max_idle_connections: 1,
+            max_retries: 2,
+            retry_backoff_milliseconds: 10,
+        }
+    }
+
+    fn email_client(base_url: String) -> PostmarkEmailClient {
+        let sender = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+        PostmarkEmailClient::new(
+            base_url,
+            sender,
+            Secret::new(Faker.fake()),
+            &connection_settings(),
+        )
+        .expect("failed to build the email client")
+    }
+
+    struct SendEmailRequesstMatcher;
+
+    impl Match for SendEmailRequesstMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlContent").is_some()
+                    && body.get("TextContent").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn should_send_email() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..9).fake();
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailRequesstMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        // Assert
+        assert_ok!(response);
+    }
+
+    #[tokio::test]
+    async fn should_send_custom_headers() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..9).fake();
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "Headers": [{ "Name": "List-Unsubscribe", "Value": "<https://example.com/u>" }]
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = email_client
+            .send_email_with_headers(
+                &subscriber_email,
+                &subject,
+                &content,
+                &content,
+                &[EmailHeader {
+                    name: "List-Unsubscribe",
+                    value: "<https://example.com/u>",
+                }],
+            )
+            .await;
+
+        // Assert
+        assert_ok!(response);
+    }
+
+    #[tokio::test]
+    async fn should_return_http_status_code_500() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..9).fake();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        // Assert
+        assert_err!(response);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_rejections_and_server_errors_are_not() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        // Act
+        let rejected = email_client
+            .send_email(&subscriber_email, "Subject", "<p>Hi</p>", "Hi")
+            .await;
+        let unavailable = email_client
+            .send_email(&subscriber_email, "Subject", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        let rejected = rejected.unwrap_err();
+        assert!(matches!(rejected, SendEmailError::Rejected(_)));
+        assert!(rejected.to_string().contains("422"));
+        assert!(matches!(
+            unavailable.unwrap_err(),
+            SendEmailError::Unavailable(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_retry_while_the_server_is_unavailable() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = email_client
+            .send_email(&subscriber_email, "Subject", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert_ok!(response);
+    }
+
+    #[tokio::test]
+    async fn should_give_up_after_the_configured_number_of_retries() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429))
+            .expect(1 + connection_settings().max_retries as u64)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = email_client
+            .send_email(&subscriber_email, "Subject", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert_err!(response);
+    }
+
+    #[tokio::test]
+    async fn should_report_an_unreachable_server_as_unavailable() {
+        // Arrange
+        // Nothing listens on a port that was just released.
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let email_client = email_client(format!("http://{}", address));
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+
+        // Act
+        let response = email_client
+            .send_email(&subscriber_email, "Subject", "<p>Hi</p>", "Hi")
+            .await;
+
+        // Assert
+        assert!(matches!(
+            response.unwrap_err(),
+            SendEmailError::Unavailable(_)
+        ));
+    }
+
+    fn batch_of(recipients: &[SubscriberEmail]) -> Vec<OutgoingEmail<'_>> {
+        recipients
+            .iter()
+            .map(|recipient| OutgoingEmail {
+                recipient,
+                subject: "Subject",
+                html_content: "<p>Hi</p>",
+                text_content: "Hi",
+                headers: &[],
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn should_report_the_outcome_of_each_message_of_a_batch() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+        let recipients = vec![
+            SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            SubscriberEmail::parse("inactive@example.com".into()).unwrap(),
+            SubscriberEmail::parse("octavia@example.com".into()).unwrap(),
+        ];
+
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(|request: &Request| {
+                let messages: Vec<serde_json::Value> =
+                    serde_json::from_slice(&request.body).unwrap();
+                let results: Vec<_> = messages
+                    .iter()
+                    .map(|message| {
+                        if message["To"] == "inactive@example.com" {
+                            serde_json::json!({
+                                "ErrorCode": 406,
+                                "Message": "You tried to send to a recipient that has been marked as inactive."
+                            })
+                        } else {
+                            serde_json::json!({ "ErrorCode": 0, "Message": "OK" })
+                        }
+                    })
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(results)
+            })
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let results = email_client.send_batch(&batch_of(&recipients)).await;
+
+        // Assert
+        assert_eq!(results.len(), 3);
+        assert_ok!(&results[0]);
+        let error = results[1].as_ref().unwrap_err();
+        assert!(matches!(error, SendEmailError::Rejected(_)));
+        assert!(error.to_string().contains("406"));
+        assert_ok!(&results[2]);
+    }
+
+    #[tokio::test]
+    async fn should_chunk_batches_to_the_provider_limit() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+        let recipients: Vec<_> = (0..501)
+            .map(|i| SubscriberEmail::parse(format!("reader{}@example.com", i)).unwrap())
+            .collect();
+
+        Mock::given(path("/email/batch"))
+            .respond_with(|request: &Request| {
+                let messages: Vec<serde_json::Value> =
+                    serde_json::from_slice(&request.body).unwrap();
+                let results: Vec<_> = messages
+                    .iter()
+                    .map(|_| serde_json::json!({ "ErrorCode": 0, "Message": "OK" }))
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(results)
+            })
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let results = email_client.send_batch(&batch_of(&recipients)).await;
+
+        // Assert
+        assert_eq!(results.len(), 501);
+        assert!(results.iter().all(Result::is_ok));
+        let batch_request = &server.received_requests().await.unwrap()[0];
+        let messages: Vec<serde_json::Value> = serde_json::from_slice(&batch_request.body).unwrap();
+        assert_eq!(messages.len(), 500);
+    }
+
+    #[tokio::test]
+    async fn should_fail_every_message_when_the_whole_batch_fails() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+        let recipients = vec![
+            SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            SubscriberEmail::parse("octavia@example.com".into()).unwrap(),
+        ];
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let results = email_client.send_batch(&batch_of(&recipients)).await;
+
+        // Assert
+        assert_eq!(results.len(), 2);
+        for result in results {
+            let error = result.unwrap_err();
+            assert!(matches!(error, SendEmailError::Unavailable(_)));
+            assert!(error.to_string().contains("500"));
+        }
+    }
+
+    #[tokio::test]
+    async fn an_unreadable_batch_response_leaves_every_message_unconfirmed() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+        let recipients = vec![
+            SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            SubscriberEmail::parse("octavia@example.com".into()).unwrap(),
+        ];
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let results = email_client.send_batch(&batch_of(&recipients)).await;
+
+        // Assert
+        assert_eq!(results.len(), 2);
+        for result in results {
+            assert!(matches!(
+                result.unwrap_err(),
+                SendEmailError::Unconfirmed(_)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_without_a_result_in_a_short_batch_response_are_unconfirmed() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+        let recipients = vec![
+            SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            SubscriberEmail::parse("octavia@example.com".into()).unwrap(),
+        ];
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([{ "ErrorCode": 0, "Message": "OK" }])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let results = email_client.send_batch(&batch_of(&recipients)).await;
+
+        // Assert
+        assert_ok!(&results[0]);
+        let error = results[1].as_ref().unwrap_err();
+        assert!(matches!(error, SendEmailError::Unconfirmed(_)));
+        assert!(error.to_string().contains("with 1 results"));
+    }
+
+    #[tokio::test]
+    async fn should_time_out_if_server_takes_long_time_to_respond() {
+        // Arrange
+        let server = MockServer::start().await;
+        let email_client = email_client(server.uri());
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake())
+            .expect("failed to get the fake sender email");
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..9).fake();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(20)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let response = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        // Assert
+        assert_err!(response);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::configuration::EmailConnectionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::{
+    build_message, send_with_retries, EmailHeader, EmailSender, SendEmailError,
+};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::PoolConfig;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::{ExposeSecret, Secret};
+
+/// Sends through an SMTP relay, upgrading the connection with STARTTLS unless told not to.
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: SubscriberEmail,
+    settings: EmailConnectionSettings,
+}
+
+impl SmtpEmailClient {
+    /// Fails if `host` is not a valid TLS server name. No connection is made until the first
+    /// email is sent.
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, Secret<String>)>,
+        starttls: bool,
+        sender: SubscriberEmail,
+        settings: &EmailConnectionSettings,
+    ) -> Result<Self, lettre::transport::smtp::Error> {
+        let mut builder = if starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        }
+        .port(port)
+        // lettre has a single timeout, covering the connection and every command.
+        .timeout(Some(settings.timeout()))
+        .pool_config(PoolConfig::new().max_size(settings.max_idle_connections as u32));
+        if let Some((username, password)) = credentials {
+            builder =
+                builder.credentials(Credentials::new(username, password.expose_secret().clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            sender,
+            settings: settings.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for SmtpEmailClient {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader<'_>],
+    ) -> Result<(), SendEmailError> {
+        let message = build_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+
+        // A 4xx reply means the server refused the message for now, so it was not accepted.
+        send_with_retries(
+            &self.settings,
+            lettre::transport::smtp::Error::is_transient,
+            || async { self.transport.send(message.clone()).await.map(|_| ()) },
+        )
+        .await
+        .map_err(|e| {
+            // 5xx replies are final; 4xx replies and connection failures are worth retrying.
+            if e.is_permanent() {
+                SendEmailError::Rejected(e.into())
+            } else {
+                SendEmailError::Unavailable(e.into())
+            }
+        })?;
+
+        Ok(())
+    }
+}
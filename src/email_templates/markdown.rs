@@ -0,0 +1,270 @@
+//! Renders a newsletter issue written in Markdown to the HTML and plain text parts of an email.
+//!
+//! Markdown lets authors embed raw HTML, so the HTML part goes through [ammonia], which keeps
+//! formatting tags and drops scripts, styles, event handlers and the like. The plain text part is
+//! rendered from the Markdown itself rather than from that HTML: links become numbered
+//! references, listed after the text, which reads better than URLs in the middle of sentences.
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use super::RenderedEmail;
+
+pub fn render_markdown(markdown: &str) -> RenderedEmail {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, Parser::new(markdown));
+
+    RenderedEmail {
+        html: ammonia::clean(&html),
+        text: markdown_to_text(markdown),
+    }
+}
+
+fn markdown_to_text(markdown: &str) -> String {
+    let mut writer = TextWriter::default();
+    for event in Parser::new(markdown) {
+        writer.handle(event);
+    }
+    writer.finish()
+}
+
+/// What an enclosing block adds in front of each of its lines, e.g. `> ` for a quote. A list
+/// item has a marker on its first line and aligns the next ones with it.
+struct LinePrefix {
+    first: String,
+    rest: String,
+    first_written: bool,
+}
+
+#[derive(Default)]
+struct TextWriter {
+    out: String,
+    prefixes: Vec<LinePrefix>,
+    at_line_start: bool,
+    // Set when a block ends, so that the next one starts after a blank line.
+    blank_line_pending: bool,
+    // The next number of each open list, `None` for bulleted ones.
+    lists: Vec<Option<u64>>,
+    // The target of each open link or image, and where its label starts in `out`.
+    open_links: Vec<(String, usize)>,
+    references: Vec<String>,
+}
+
+impl TextWriter {
+    fn handle(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::Code(text) => self.write(&text),
+            Event::SoftBreak | Event::HardBreak => self.new_line(),
+            Event::Rule => {
+                self.start_block();
+                self.write("----");
+                self.end_block();
+            }
+            // Raw HTML tags are left out of the plain text.
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Paragraph | Tag::Heading { .. } => self.start_block(),
+            Tag::BlockQuote(_) => {
+                self.start_block();
+                self.push_prefix("> ", "> ");
+            }
+            Tag::CodeBlock(_) => {
+                self.start_block();
+                self.push_prefix("    ", "    ");
+            }
+            Tag::List(start) => {
+                self.start_block();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                if !self.at_line_start {
+                    self.new_line();
+                }
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                let indent = " ".repeat(marker.len());
+                self.push_prefix(&marker, &indent);
+            }
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.open_links
+                    .push((dest_url.into_string(), self.out.len()));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Heading(_) => self.end_block(),
+            TagEnd::BlockQuote(_) | TagEnd::CodeBlock => {
+                self.prefixes.pop();
+                self.end_block();
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                self.end_block();
+            }
+            TagEnd::Item => {
+                if !self.at_line_start {
+                    self.new_line();
+                }
+                self.prefixes.pop();
+            }
+            TagEnd::Link | TagEnd::Image => {
+                if let Some((target, label_start)) = self.open_links.pop() {
+                    self.write_reference(target, label_start);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Follows a link label with the number of its target, unless the label is the target.
+    fn write_reference(&mut self, target: String, label_start: usize) {
+        let label = &self.out[label_start..];
+        if label == target || target.strip_prefix("mailto:") == Some(label) {
+            return;
+        }
+
+        let number = match self.references.iter().position(|r| *r == target) {
+            Some(index) => index + 1,
+            None => {
+                self.references.push(target);
+                self.references.len()
+            }
+        };
+        self.write(&format!(" [{}]", number));
+    }
+
+    fn push_prefix(&mut self, first: &str, rest: &str) {
+        self.prefixes.push(LinePrefix {
+            first: first.to_string(),
+            rest: rest.to_string(),
+            first_written: false,
+        });
+    }
+
+    fn start_block(&mut self) {
+        if !self.out.is_empty() {
+            if !self.at_line_start {
+                self.new_line();
+            }
+            if self.blank_line_pending {
+                let prefix: String = self.prefixes.iter().map(|p| p.rest.as_str()).collect();
+                self.out.push_str(prefix.trim_end());
+                self.new_line();
+            }
+        }
+        self.blank_line_pending = false;
+    }
+
+    fn end_block(&mut self) {
+        if !self.at_line_start {
+            self.new_line();
+        }
+        self.blank_line_pending = true;
+    }
+
+    fn new_line(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    fn write(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.new_line();
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.at_line_start || self.out.is_empty() {
+                for prefix in &mut self.prefixes {
+                    if prefix.first_written {
+                        self.out.push_str(&prefix.rest);
+                    } else {
+                        self.out.push_str(&prefix.first);
+                        prefix.first_written = true;
+                    }
+                }
+                self.at_line_start = false;
+            }
+            self.out.push_str(line);
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut text = self.out.trim_end().to_string();
+        if !self.references.is_empty() {
+            text.push('\n');
+            for (index, target) in self.references.iter().enumerate() {
+                text.push_str(&format!("\n[{}] {}", index + 1, target));
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    #[test]
+    fn markdown_is_rendered_to_html() {
+        let email = render_markdown("# Issue 1\n\nHello *world*!");
+
+        assert_eq!(
+            email.html,
+            "<h1>Issue 1</h1>\n<p>Hello <em>world</em>!</p>\n"
+        );
+    }
+
+    #[test]
+    fn unsafe_html_is_removed() {
+        let email = render_markdown(
+            "Hi<script>alert(1)</script> <a href=\"https://example.com\" onclick=\"steal()\">there</a>",
+        );
+
+        assert!(!email.html.contains("script"));
+        assert!(!email.html.contains("onclick"));
+        assert!(email.html.contains("https://example.com"));
+    }
+
+    #[test]
+    fn links_become_numbered_references_in_the_text() {
+        let email = render_markdown(
+            "Read [the post](https://example.com/a), [the reply](https://example.com/b) \
+            and [the post](https://example.com/a) again.\n\nOr visit <https://example.com>.",
+        );
+
+        assert_eq!(
+            email.text,
+            "Read the post [1], the reply [2] and the post [1] again.\n\n\
+            Or visit https://example.com.\n\n\
+            [1] https://example.com/a\n\
+            [2] https://example.com/b"
+        );
+    }
+
+    #[test]
+    fn blocks_keep_their_structure_in_the_text() {
+        let email = render_markdown(
+            "## Agenda\n\n1. Intro\n2. Updates\n   - web\n   - mobile\n\n> Quoted\n> text\n\n    let x = 1;\n",
+        );
+
+        assert_eq!(
+            email.text,
+            "Agenda\n\n1. Intro\n2. Updates\n   - web\n   - mobile\n\n> Quoted\n> text\n\n    let x = 1;"
+        );
+    }
+}
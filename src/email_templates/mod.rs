@@ -0,0 +1,371 @@
+//! Named email templates, rendered with [minijinja](https://docs.rs/minijinja).
+//!
+//! Templates are read from the directory in `EmailTemplateSettings`, and rows of the
+//! `email_templates` table replace the file with the same name. Each email has a
+//! `<name>.html` template and, optionally, a `<name>.txt` one; without it the plain text part
+//! is derived from the HTML. Templates can `{% extends %}` a layout and `{% include %}`
+//! partials. Variables are HTML-escaped in `.html` templates unless marked `|safe`.
+//!
+//! The rendered HTML then has its CSS inlined, is sanitized and has relative URLs made
+//! absolute, see [`HtmlPreparer`].
+//!
+//! Everything is loaded, compiled and rendered once with example values at startup, so a
+//! syntax error or a misspelt variable stops the application instead of failing a send.
+
+mod markdown;
+mod plain_text;
+mod prepare;
+
+pub use markdown::render_markdown;
+pub use plain_text::html_to_text;
+pub use prepare::{HtmlPreparer, GMAIL_CLIPPING_THRESHOLD};
+
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::path::Path;
+
+use crate::configuration::EmailTemplateSettings;
+use crate::routes::error_chain_fmt;
+
+/// The variables of one kind of email.
+pub trait EmailTemplate: Serialize {
+    /// The template names, without their extension.
+    const NAME: &'static str;
+
+    /// Values to render the template with when checking it at startup.
+    fn example() -> Self;
+}
+
+#[derive(Serialize)]
+pub struct ConfirmationEmail<'a> {
+    pub subscriber_name: &'a str,
+    pub confirmation_link: &'a str,
+}
+
+impl EmailTemplate for ConfirmationEmail<'_> {
+    const NAME: &'static str = "confirmation";
+
+    fn example() -> Self {
+        Self {
+            subscriber_name: "Ursula",
+            confirmation_link: "https://example.com/subscriptions/confirm?subscription_token=x",
+        }
+    }
+}
+
+/// `html_content` is the issue as written by the author, and is inserted as is.
+#[derive(Serialize)]
+pub struct NewsletterEmail<'a> {
+    pub subscriber_name: &'a str,
+    pub title: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub unsubscribe_link: &'a str,
+}
+
+impl EmailTemplate for NewsletterEmail<'_> {
+    const NAME: &'static str = "newsletter";
+
+    fn example() -> Self {
+        Self {
+            subscriber_name: "Ursula",
+            title: "Issue #1",
+            html_content: "<p>Hello</p>",
+            text_content: "Hello",
+            unsubscribe_link: "https://example.com/subscriptions/unsubscribe?token=x",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PasswordResetEmail<'a> {
+    pub reset_link: &'a str,
+    pub expires_in_minutes: i64,
+}
+
+impl EmailTemplate for PasswordResetEmail<'_> {
+    const NAME: &'static str = "password_reset";
+
+    fn example() -> Self {
+        Self {
+            reset_link: "https://example.com/password_reset/confirm?token=x",
+            expires_in_minutes: 30,
+        }
+    }
+}
+
+pub struct RenderedEmail {
+    pub html: String,
+    pub text: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum TemplateError {
+    #[error("failed to read the email templates")]
+    IoError(#[from] std::io::Error),
+    #[error("failed to load the email templates from the database")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("invalid email template")]
+    InvalidTemplate(#[from] minijinja::Error),
+    #[error("invalid base URL for email links")]
+    InvalidBaseUrl(#[from] ammonia::url::ParseError),
+    #[error("failed to inline the CSS of an email")]
+    CssError(#[from] css_inline::InlineError),
+}
+
+impl std::fmt::Debug for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+pub struct EmailTemplates {
+    environment: Environment<'static>,
+    preparer: HtmlPreparer,
+}
+
+impl EmailTemplates {
+    /// Loads the templates from disk and the database, then checks them.
+    #[tracing::instrument(name = "Load email templates", skip_all)]
+    pub async fn load(
+        settings: &EmailTemplateSettings,
+        base_url: &str,
+        connection_pool: &PgPool,
+    ) -> Result<Self, TemplateError> {
+        let mut sources = Vec::new();
+        read_directory(Path::new(&settings.directory), "", &mut sources)?;
+
+        let overrides = sqlx::query!(r#"SELECT name, source FROM email_templates"#)
+            .fetch_all(connection_pool)
+            .await?;
+        for row in overrides {
+            tracing::info!(template = %row.name, "Using the email template from the database");
+            sources.retain(|(name, _)| *name != row.name);
+            sources.push((row.name, row.source));
+        }
+
+        Self::from_sources(sources, base_url)
+    }
+
+    /// Compiles `(name, source)` pairs, then checks that every email renders. Relative URLs in
+    /// emails are resolved against `base_url`.
+    pub fn from_sources(
+        sources: impl IntoIterator<Item = (String, String)>,
+        base_url: &str,
+    ) -> Result<Self, TemplateError> {
+        let mut environment = Environment::new();
+        environment.set_undefined_behavior(UndefinedBehavior::Strict);
+        environment.set_formatter(escape_formatter);
+        for (name, source) in sources {
+            environment.add_template_owned(name, source)?;
+        }
+
+        let templates = Self {
+            environment,
+            preparer: HtmlPreparer::new(base_url)?,
+        };
+        templates.render(&ConfirmationEmail::example())?;
+        templates.render(&NewsletterEmail::example())?;
+        templates.render(&PasswordResetEmail::example())?;
+
+        Ok(templates)
+    }
+
+    pub fn render<T: EmailTemplate>(&self, email: &T) -> Result<RenderedEmail, TemplateError> {
+        let html = self
+            .environment
+            .get_template(&format!("{}.html", T::NAME))?
+            .render(email)?;
+        let html = self.preparer.prepare(&html)?;
+        if html.len() > GMAIL_CLIPPING_THRESHOLD {
+            tracing::warn!(
+                template = T::NAME,
+                size = html.len(),
+                "The email is larger than Gmail's clipping threshold, Gmail will cut it short"
+            );
+        }
+        let text = match self.environment.get_template(&format!("{}.txt", T::NAME)) {
+            Ok(template) => template.render(email)?,
+            Err(e) if e.kind() == minijinja::ErrorKind::TemplateNotFound => html_to_text(&html),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(RenderedEmail { html, text })
+    }
+}
+
+/// Template names are paths relative to the template directory, with `/` separators.
+fn read_directory(
+    directory: &Path,
+    prefix: &str,
+    sources: &mut Vec<(String, String)>,
+) -> Result<(), std::io::Error> {
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            read_directory(&entry.path(), &format!("{}/", name), sources)?;
+        } else {
+            sources.push((name, std::fs::read_to_string(entry.path())?));
+        }
+    }
+    Ok(())
+}
+
+// minijinja's own HTML escaping also escapes `/`, which mangles every link we insert. Quotes,
+// `&`, `<` and `>` are enough for text and quoted attribute values.
+fn escape_formatter(
+    out: &mut minijinja::Output<'_>,
+    state: &minijinja::State<'_, '_>,
+    value: &minijinja::Value,
+) -> Result<(), minijinja::Error> {
+    if !matches!(state.auto_escape(), AutoEscape::Html) || value.is_safe() || value.is_none() {
+        return minijinja::escape_formatter(out, state, value);
+    }
+
+    let mut escaped = String::new();
+    for c in value.to_string().chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    out.write_str(&escaped).map_err(minijinja::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfirmationEmail, EmailTemplates, NewsletterEmail, TemplateError};
+
+    const BASE_URL: &str = "https://example.com/";
+
+    fn sources(overrides: &[(&str, &str)]) -> Vec<(String, String)> {
+        let mut sources = vec![
+            (
+                "layout.html",
+                "<html><head><title>{% block title %}{% endblock %}</title></head>\
+                <body>{% block content %}{% endblock %}{% include \"footer.html\" %}</body></html>",
+            ),
+            ("footer.html", "<p>Bye</p>"),
+            (
+                "confirmation.html",
+                "{% extends \"layout.html\" %}{% block content %}<p>Hi {{ subscriber_name }}, \
+                <a href=\"{{ confirmation_link }}\">confirm</a></p>{% endblock %}",
+            ),
+            (
+                "newsletter.html",
+                "<h1>{{ title }}</h1>{{ html_content|safe }}<a href=\"{{ unsubscribe_link }}\">Unsubscribe</a>",
+            ),
+            (
+                "newsletter.txt",
+                "{{ title }}\n\n{{ text_content }}\n\nUnsubscribe: {{ unsubscribe_link }}",
+            ),
+            (
+                "password_reset.html",
+                "<a href=\"{{ reset_link }}\">Reset</a> within {{ expires_in_minutes }} minutes",
+            ),
+        ];
+        for (name, source) in overrides {
+            sources.retain(|(existing, _)| existing != name);
+            sources.push((name, source));
+        }
+        sources
+            .into_iter()
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn variables_are_escaped_in_html_but_links_survive() {
+        let templates = EmailTemplates::from_sources(sources(&[]), BASE_URL).unwrap();
+
+        let email = templates
+            .render(&ConfirmationEmail {
+                subscriber_name: "<script>alert('hi')</script>",
+                confirmation_link: "https://example.com/confirm?a=1",
+            })
+            .unwrap();
+
+        assert!(email
+            .html
+            .contains("Hi &lt;script&gt;alert('hi')&lt;/script&gt;"));
+        assert!(email
+            .html
+            .contains(r#"href="https://example.com/confirm?a=1""#));
+        assert!(email.html.ends_with("<p>Bye</p></body></html>"));
+    }
+
+    #[test]
+    fn plain_text_is_derived_from_the_html_without_a_txt_template() {
+        let templates = EmailTemplates::from_sources(sources(&[]), BASE_URL).unwrap();
+
+        let email = templates
+            .render(&ConfirmationEmail {
+                subscriber_name: "Ursula & co",
+                confirmation_link: "https://example.com/confirm",
+            })
+            .unwrap();
+
+        assert_eq!(
+            email.text,
+            "Hi Ursula & co, confirm (https://example.com/confirm)\n\nBye"
+        );
+    }
+
+    #[test]
+    fn txt_templates_are_used_for_the_plain_text_when_present() {
+        let templates = EmailTemplates::from_sources(sources(&[]), BASE_URL).unwrap();
+
+        let email = templates
+            .render(&NewsletterEmail {
+                subscriber_name: "Ursula",
+                title: "Issue <1>",
+                html_content: "<p>Hello</p>",
+                text_content: "Hello",
+                unsubscribe_link: "https://example.com/u",
+            })
+            .unwrap();
+
+        assert!(email.html.contains("<h1>Issue &lt;1&gt;</h1><p>Hello</p>"));
+        assert_eq!(
+            email.text,
+            "Issue <1>\n\nHello\n\nUnsubscribe: https://example.com/u"
+        );
+    }
+
+    #[test]
+    fn misspelt_variables_are_rejected_up_front() {
+        let result = EmailTemplates::from_sources(
+            sources(&[(
+                "confirmation.html",
+                "<a href=\"{{ confirmation_lnk }}\">confirm</a>",
+            )]),
+            BASE_URL,
+        );
+
+        assert!(matches!(result, Err(TemplateError::InvalidTemplate(_))));
+    }
+
+    #[test]
+    fn syntax_errors_are_rejected_up_front() {
+        let result =
+            EmailTemplates::from_sources(sources(&[("footer.html", "<p>{% if %}</p>")]), BASE_URL);
+
+        assert!(matches!(result, Err(TemplateError::InvalidTemplate(_))));
+    }
+
+    #[test]
+    fn missing_templates_are_rejected_up_front() {
+        let mut sources = sources(&[]);
+        sources.retain(|(name, _)| name != "password_reset.html");
+
+        let result = EmailTemplates::from_sources(sources, BASE_URL);
+
+        assert!(matches!(result, Err(TemplateError::InvalidTemplate(_))));
+    }
+}
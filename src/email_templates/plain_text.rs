@@ -0,0 +1,288 @@
+//! Derives the plain text part of an email from its HTML part, for templates that do not come
+//! with a `.txt` version. Links keep their target, as `label (https://…)`, since a plain text
+//! reader has no other way to follow them.
+
+const BLOCK_TAGS: [&str; 17] = [
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "table",
+    "tr",
+    "blockquote",
+    "section",
+    "header",
+    "footer",
+    "hr",
+];
+// Their content is not meant to be read.
+const HIDDEN_TAGS: [&str; 4] = ["head", "title", "style", "script"];
+
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::new();
+    let mut hidden_depth = 0usize;
+    // The target of the link being written, and where its label starts in `out`.
+    let mut open_link: Option<(String, usize)> = None;
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        if hidden_depth == 0 {
+            push_text(&mut out, &rest[..start]);
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            // A lone `<` is text.
+            if hidden_depth == 0 {
+                push_text(&mut out, rest);
+            }
+            rest = "";
+            break;
+        };
+        let tag = Tag::parse(&rest[1..end]);
+        rest = &rest[end + 1..];
+
+        if HIDDEN_TAGS.contains(&tag.name.as_str()) {
+            if tag.is_closing {
+                hidden_depth = hidden_depth.saturating_sub(1);
+            } else {
+                hidden_depth += 1;
+            }
+            continue;
+        }
+        if hidden_depth > 0 {
+            continue;
+        }
+
+        match (tag.name.as_str(), tag.is_closing) {
+            ("br", _) => out.push('\n'),
+            ("li", false) => out.push_str("\n- "),
+            ("a", false) => open_link = tag.href.map(|href| (href, out.len())),
+            ("a", true) => {
+                if let Some((href, label_start)) = open_link.take() {
+                    let label = out[label_start..].trim();
+                    if label.is_empty() {
+                        out.push_str(&href);
+                    } else if label != href {
+                        out.push_str(&format!(" ({})", href));
+                    }
+                }
+            }
+            (name, _) if BLOCK_TAGS.contains(&name) => out.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    if hidden_depth == 0 {
+        push_text(&mut out, rest);
+    }
+
+    normalize_whitespace(&out)
+}
+
+struct Tag {
+    name: String,
+    is_closing: bool,
+    href: Option<String>,
+}
+
+impl Tag {
+    /// Parses what is between `<` and `>`.
+    fn parse(tag: &str) -> Self {
+        let (is_closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        let href = if name == "a" {
+            attribute(&tag[name_end..], "href")
+        } else {
+            None
+        };
+
+        Self {
+            name,
+            is_closing,
+            href,
+        }
+    }
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return None;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let attribute_name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, remainder) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let value = &value[1..];
+                        let end = value.find(quote).unwrap_or(value.len());
+                        (&value[..end], value.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                        (&value[..end], &value[end..])
+                    }
+                };
+                rest = remainder;
+                value
+            }
+            None => "",
+        };
+        if attribute_name.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value));
+        }
+    }
+}
+
+/// Appends text content, with runs of whitespace collapsed the way a browser would.
+fn push_text(out: &mut String, text: &str) {
+    for c in decode_entities(text).chars() {
+        if c.is_whitespace() && c != '\u{a0}' {
+            if !out.ends_with([' ', '\n']) {
+                out.push(' ');
+            }
+        } else if c == '\u{a0}' {
+            out.push(' ');
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end + 1]).map(|c| (c, end + 2)));
+        match entity {
+            Some((c, length)) => {
+                decoded.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Trims every line and keeps at most one blank line between paragraphs.
+fn normalize_whitespace(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !normalized.is_empty() {
+            normalized.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        normalized.push_str(line);
+        blank_lines = 0;
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::html_to_text;
+
+    #[test]
+    fn paragraphs_are_separated_by_a_blank_line() {
+        let html = "<p>Hello,\n   world.</p><p>Second paragraph</p>";
+
+        assert_eq!(html_to_text(html), "Hello, world.\n\nSecond paragraph");
+    }
+
+    #[test]
+    fn links_keep_their_target() {
+        let html = r#"<p>Click <a href="https://example.com/confirm?a=1&amp;b=2">here</a>.</p>"#;
+
+        assert_eq!(
+            html_to_text(html),
+            "Click here (https://example.com/confirm?a=1&b=2)."
+        );
+    }
+
+    #[test]
+    fn links_labelled_with_their_target_are_not_repeated() {
+        let html = r#"<a href="https://example.com">https://example.com</a>"#;
+
+        assert_eq!(html_to_text(html), "https://example.com");
+    }
+
+    #[test]
+    fn head_styles_scripts_and_comments_are_dropped() {
+        let html = r#"<!DOCTYPE html><html><head><title>Welcome</title>
+            <style>p { color: red; }</style></head>
+            <body><!-- tracking --><script>alert(1)</script><p>Body</p></body></html>"#;
+
+        assert_eq!(html_to_text(html), "Body");
+    }
+
+    #[test]
+    fn lists_and_line_breaks_are_kept() {
+        let html = "<p>Topics:</p><ul><li>Rust</li><li>Email</li></ul><p>a<br>b</p>";
+
+        assert_eq!(html_to_text(html), "Topics:\n\n- Rust\n- Email\n\na\nb");
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        let html = "<p>Fish &amp; chips &lt;3 &#39;quoted&#x27; &copy;</p>";
+
+        assert_eq!(html_to_text(html), "Fish & chips <3 'quoted' &copy;");
+    }
+}
@@ -0,0 +1,129 @@
+//! Turns rendered HTML into something mail clients display as intended.
+//!
+//! Many clients, Gmail among them, drop `<style>` blocks, so their rules are first copied into
+//! the `style` attribute of the elements they match. The result is then sanitized: issues
+//! published as raw HTML end up in emails too, and scripts, event handlers or `javascript:` links
+//! have no business there. Relative links and images are made absolute against the public base
+//! URL, since an email has no page address to resolve them from.
+
+use ammonia::{Url, UrlRelative};
+use css_inline::CSSInliner;
+use std::borrow::Cow;
+
+use super::TemplateError;
+
+/// Gmail only shows the beginning of messages larger than this, behind a "View entire message"
+/// link.
+pub const GMAIL_CLIPPING_THRESHOLD: usize = 102 * 1024;
+
+// Presentational attributes that table-based email layouts still rely on.
+const EMAIL_ATTRIBUTES: [&str; 9] = [
+    "style",
+    "align",
+    "valign",
+    "width",
+    "height",
+    "bgcolor",
+    "border",
+    "cellpadding",
+    "cellspacing",
+];
+
+pub struct HtmlPreparer {
+    inliner: CSSInliner<'static>,
+    sanitizer: ammonia::Builder<'static>,
+}
+
+impl HtmlPreparer {
+    pub fn new(base_url: &str) -> Result<Self, TemplateError> {
+        let inliner = CSSInliner::options()
+            .keep_style_tags(false)
+            .keep_link_tags(false)
+            .load_remote_stylesheets(false)
+            .build();
+
+        let mut sanitizer = ammonia::Builder::default();
+        sanitizer
+            .add_generic_attributes(EMAIL_ATTRIBUTES)
+            .add_clean_content_tags(["title"])
+            .url_relative(UrlRelative::RewriteWithBase(Url::parse(base_url)?))
+            .attribute_filter(|_, attribute, value| {
+                if attribute == "style" && is_unsafe_style(value) {
+                    None
+                } else {
+                    Some(Cow::Borrowed(value))
+                }
+            });
+
+        Ok(Self { inliner, sanitizer })
+    }
+
+    /// Mail clients show the subject rather than the `<title>`, so only the body is kept.
+    pub fn prepare(&self, html: &str) -> Result<String, TemplateError> {
+        let inlined = self.inliner.inline(html)?;
+        let body = self.sanitizer.clean(&inlined);
+
+        Ok(format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head><body>{}</body></html>",
+            body
+        ))
+    }
+}
+
+// Older clients evaluate scripts in CSS.
+fn is_unsafe_style(style: &str) -> bool {
+    let style = style.to_ascii_lowercase();
+    style.contains("expression(") || style.contains("javascript:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HtmlPreparer;
+
+    fn prepare(html: &str) -> String {
+        HtmlPreparer::new("https://example.com/")
+            .unwrap()
+            .prepare(html)
+            .unwrap()
+    }
+
+    #[test]
+    fn style_blocks_are_inlined() {
+        let html = prepare(
+            "<html><head><style>p { color: red } .note { font-weight: bold }</style></head>\
+            <body><p class=\"note\">Hi</p></body></html>",
+        );
+
+        assert!(html.contains(r#"<p style="color: red;font-weight: bold;">Hi</p>"#));
+        assert!(!html.contains("<style"));
+    }
+
+    #[test]
+    fn scripts_and_dangerous_attributes_are_removed() {
+        let html = prepare(
+            "<p onclick=\"steal()\">Hi<script>steal()</script></p>\
+            <a href=\"javascript:steal()\">x</a>\
+            <p style=\"background: expression(steal())\">y</p>",
+        );
+
+        assert!(!html.contains("steal"));
+        assert!(html.contains("<p>Hi</p><a rel=\"noopener noreferrer\">x</a><p>y</p>"));
+    }
+
+    #[test]
+    fn relative_urls_are_made_absolute() {
+        let html = prepare(
+            "<a href=\"/archive\">Archive</a><img src=\"images/logo.png\" alt=\"\">\
+            <a href=\"https://other.example.org/\">Other</a>",
+        );
+
+        assert!(html.contains(r#"href="https://example.com/archive""#));
+        assert!(html.contains(r#"src="https://example.com/images/logo.png""#));
+        assert!(html.contains(r#"href="https://other.example.org/""#));
+    }
+
+    #[test]
+    fn invalid_base_urls_are_rejected() {
+        assert!(HtmlPreparer::new("not a url").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+#[derive(Debug)]
+pub struct IdempotencyKey(String);
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum IdempotencyKeyError {
+    #[error("idempotency key must not be empty")]
+    Empty,
+    #[error("idempotency key must be shorter than 50 characters")]
+    TooLong,
+}
+
+impl TryFrom<String> for IdempotencyKey {
+    type Error = IdempotencyKeyError;
+
+    fn try_from(key: String) -> Result<Self, Self::Error> {
+        if key.is_empty() {
+            return Err(IdempotencyKeyError::Empty);
+        }
+
+        if key.chars().count() >= 50 {
+            return Err(IdempotencyKeyError::TooLong);
+        }
+
+        Ok(Self(key))
+    }
+}
+
+impl AsRef<str> for IdempotencyKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdempotencyKey, IdempotencyKeyError};
+    use claims::{assert_err_eq, assert_ok};
+
+    #[test]
+    fn an_empty_key_is_invalid() {
+        assert_err_eq!(
+            IdempotencyKey::try_from(String::new()),
+            IdempotencyKeyError::Empty
+        );
+    }
+
+    #[test]
+    fn a_key_of_50_characters_is_invalid() {
+        assert_err_eq!(
+            IdempotencyKey::try_from("a".repeat(50)),
+            IdempotencyKeyError::TooLong
+        );
+    }
+
+    #[test]
+    fn key_length_is_counted_in_characters() {
+        assert_ok!(IdempotencyKey::try_from("é".repeat(49)));
+        assert_err_eq!(
+            IdempotencyKey::try_from("é".repeat(50)),
+            IdempotencyKeyError::TooLong
+        );
+    }
+
+    #[test]
+    fn a_uuid_key_is_valid() {
+        assert_ok!(IdempotencyKey::try_from(uuid::Uuid::new_v4().to_string()));
+    }
+}
@@ -0,0 +1,5 @@
+mod key;
+mod persistence;
+
+pub use key::{IdempotencyKey, IdempotencyKeyError};
+pub use persistence::{save_response, try_processing, IdempotencyError, NextAction};
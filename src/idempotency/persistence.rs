@@ -0,0 +1,146 @@
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use sqlx::postgres::PgHasArrayType;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::IdempotencyKey;
+use crate::routes::error_chain_fmt;
+
+#[derive(Debug, sqlx::Type)]
+#[sqlx(type_name = "header_pair")]
+struct HeaderPairRecord {
+    name: String,
+    value: Vec<u8>,
+}
+
+impl PgHasArrayType for HeaderPairRecord {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_header_pair")
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum IdempotencyError {
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("failed to read the response body: {0}")]
+    ResponseBodyError(String),
+    #[error("no response was saved for the idempotency key")]
+    MissingSavedResponse,
+    #[error("the saved response has an invalid status code: {0}")]
+    InvalidStatusCode(i16),
+}
+
+impl std::fmt::Debug for IdempotencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum NextAction {
+    StartProcessing(Transaction<'static, Postgres>),
+    ReturnSavedResponse(HttpResponse),
+}
+
+// A concurrent request carrying the same key blocks on the INSERT below until the first
+// request commits, and then finds the response it saved.
+pub async fn try_processing(
+    connection_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<NextAction, IdempotencyError> {
+    let mut transaction = connection_pool.begin().await?;
+    let n_inserted_rows = sqlx::query!(
+        r#"INSERT INTO idempotency(user_id, idempotency_key, created_at)
+        VALUES($1, $2, now())
+        ON CONFLICT DO NOTHING"#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+
+    if n_inserted_rows > 0 {
+        return Ok(NextAction::StartProcessing(transaction));
+    }
+
+    let saved_response = get_saved_response(connection_pool, idempotency_key, user_id)
+        .await?
+        .ok_or(IdempotencyError::MissingSavedResponse)?;
+
+    Ok(NextAction::ReturnSavedResponse(saved_response))
+}
+
+async fn get_saved_response(
+    connection_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<HttpResponse>, IdempotencyError> {
+    let saved_response = sqlx::query!(
+        r#"SELECT
+            response_status_code AS "response_status_code!",
+            response_headers AS "response_headers!: Vec<HeaderPairRecord>",
+            response_body AS "response_body!"
+        FROM idempotency
+        WHERE user_id = $1 AND idempotency_key = $2"#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(connection_pool)
+    .await?;
+
+    match saved_response {
+        Some(r) => {
+            let status_code = StatusCode::from_u16(r.response_status_code as u16)
+                .map_err(|_| IdempotencyError::InvalidStatusCode(r.response_status_code))?;
+            let mut response = HttpResponse::build(status_code);
+            for HeaderPairRecord { name, value } in r.response_headers {
+                response.append_header((name, value));
+            }
+
+            Ok(Some(response.body(r.response_body)))
+        }
+        None => Ok(None),
+    }
+}
+
+pub async fn save_response(
+    mut transaction: Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    http_response: HttpResponse,
+) -> Result<HttpResponse, IdempotencyError> {
+    let (response_head, body) = http_response.into_parts();
+    let body = to_bytes(body)
+        .await
+        .map_err(|e| IdempotencyError::ResponseBodyError(e.to_string()))?;
+    let status_code = response_head.status().as_u16() as i16;
+    let headers = response_head
+        .headers()
+        .iter()
+        .map(|(name, value)| HeaderPairRecord {
+            name: name.as_str().to_owned(),
+            value: value.as_bytes().to_owned(),
+        })
+        .collect::<Vec<_>>();
+
+    sqlx::query_unchecked!(
+        r#"UPDATE idempotency
+        SET response_status_code = $3, response_headers = $4, response_body = $5
+        WHERE user_id = $1 AND idempotency_key = $2"#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code,
+        headers,
+        body.as_ref()
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(response_head.set_body(body).map_into_boxed_body())
+}
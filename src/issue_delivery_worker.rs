@@ -0,0 +1,619 @@
+use chrono::{DateTime, Utc};
+use rand::{thread_rng, Rng};
+use secrecy::Secret;
+use sqlx::{Connection, PgPool, Postgres, Transaction};
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Span;
+use uuid::Uuid;
+
+use crate::click_tracking::ClickTracker;
+use crate::configuration::{IssueDeliverySettings, TrackingSettings};
+use crate::domain::{OpenToken, SubscriberEmail, SubscriptionStatus, UnsubscribeToken};
+use crate::email_client::{EmailHeader, EmailSender, OutgoingEmail, SendEmailError};
+use crate::email_templates::{EmailTemplates, NewsletterEmail, TemplateError};
+use crate::routes::error_chain_fmt;
+use crate::suppression_list::{send_batch_unless_suppressed, Delivery};
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+#[derive(thiserror::Error)]
+pub enum DeliveryError {
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl std::fmt::Debug for DeliveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum DeliveryFailure {
+    Transient(String),
+    Permanent(String),
+}
+
+impl DeliveryFailure {
+    fn from_send_error(error: &SendEmailError) -> Self {
+        match error {
+            SendEmailError::Rejected(_) | SendEmailError::Unconfirmed(_) => {
+                DeliveryFailure::Permanent(error.to_string())
+            }
+            SendEmailError::Unavailable(_) => DeliveryFailure::Transient(error.to_string()),
+        }
+    }
+}
+
+pub async fn run_worker_until_stopped(
+    connection_pool: PgPool,
+    email_client: Arc<dyn EmailSender>,
+    email_templates: Arc<EmailTemplates>,
+    settings: IssueDeliverySettings,
+    tracking_settings: TrackingSettings,
+    base_url: String,
+    hmac_secret: Secret<String>,
+) {
+    loop {
+        match try_execute_task(
+            &connection_pool,
+            email_client.as_ref(),
+            &email_templates,
+            &settings,
+            &tracking_settings,
+            &base_url,
+            &hmac_secret,
+        )
+        .await
+        {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(n_tasks = tracing::field::Empty), err)]
+pub async fn try_execute_task(
+    connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    email_templates: &EmailTemplates,
+    settings: &IssueDeliverySettings,
+    tracking_settings: &TrackingSettings,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+) -> Result<ExecutionOutcome, DeliveryError> {
+    let (mut transaction, tasks) = dequeue_tasks(connection_pool, settings.batch_size).await?;
+    if tasks.is_empty() {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    Span::current().record("n_tasks", tasks.len());
+
+    // Subscribers may have left between the issue being published and this delivery.
+    let subscribers = get_confirmed_subscribers(connection_pool, &tasks).await?;
+    let mut issues = HashMap::new();
+    for task in &tasks {
+        if let Entry::Vacant(entry) = issues.entry(task.newsletter_issue_id) {
+            entry.insert(get_issue(connection_pool, task.newsletter_issue_id).await?);
+        }
+    }
+
+    let mut outcomes: Vec<Option<Result<(), DeliveryFailure>>> = Vec::with_capacity(tasks.len());
+    let mut deliveries = Vec::new();
+    for (index, task) in tasks.iter().enumerate() {
+        let Some(subscriber) = subscribers.get(&task.subscriber_email) else {
+            tracing::info!(
+                subscriber_email = %task.subscriber_email,
+                "The subscriber is no longer confirmed. Skipping delivery"
+            );
+            outcomes.push(Some(Ok(())));
+            continue;
+        };
+        let subscriber_email = match SubscriberEmail::parse(task.subscriber_email.clone()) {
+            Ok(subscriber_email) => subscriber_email,
+            Err(error) => {
+                outcomes.push(Some(Err(DeliveryFailure::Permanent(error.to_string()))));
+                continue;
+            }
+        };
+        let issue = &issues[&task.newsletter_issue_id];
+        let links = DeliveryLinks {
+            unsubscribe: unsubscribe_link(base_url, subscriber.id, hmac_secret),
+            click_tracker: (tracking_settings.clicks && issue.track_clicks).then(|| {
+                ClickTracker::new(
+                    base_url,
+                    hmac_secret,
+                    subscriber.id,
+                    task.newsletter_issue_id,
+                )
+            }),
+            open_tracking: tracking_settings.opens.then(|| {
+                open_tracking_link(
+                    base_url,
+                    subscriber.id,
+                    task.newsletter_issue_id,
+                    hmac_secret,
+                )
+            }),
+        };
+        let delivery = IssueDelivery::render(
+            email_templates,
+            index,
+            subscriber_email,
+            subscriber,
+            issue,
+            links,
+        );
+        match delivery {
+            Ok(delivery) => {
+                outcomes.push(None);
+                deliveries.push(delivery);
+            }
+            Err(error) => {
+                outcomes.push(Some(Err(DeliveryFailure::Permanent(format!(
+                    "{:#}",
+                    error
+                )))));
+            }
+        }
+    }
+
+    let headers: Vec<_> = deliveries.iter().map(IssueDelivery::headers).collect();
+    let emails: Vec<_> = deliveries
+        .iter()
+        .zip(&headers)
+        .map(|(delivery, headers)| delivery.email(headers))
+        .collect();
+    let sent_at = Utc::now();
+    let results = send_batch_unless_suppressed(connection_pool, email_client, &emails).await?;
+
+    // The emails are out now. Each write below gets its own savepoint and its failures are only
+    // logged: a failed one must not roll the whole batch back, which would send it again.
+    for (delivery, result) in deliveries.iter().zip(results) {
+        let task = &tasks[delivery.task_index];
+        if let Ok(Delivery::Sent) = result {
+            let recorded = async {
+                let mut savepoint = transaction.begin().await?;
+                record_delivery(&mut savepoint, task, delivery.subscriber_id, sent_at).await?;
+                savepoint.commit().await
+            }
+            .await;
+            if let Err(e) = recorded {
+                tracing::error!(
+                    error.message = %e,
+                    subscriber_email = %task.subscriber_email,
+                    "Failed to record a delivery",
+                );
+            }
+        }
+        outcomes[delivery.task_index] = Some(match result {
+            Ok(Delivery::Sent | Delivery::Suppressed) => Ok(()),
+            Err(e) => Err(DeliveryFailure::from_send_error(&e)),
+        });
+    }
+
+    for (task, outcome) in tasks.iter().zip(outcomes) {
+        let outcome = outcome.expect("every task has an outcome");
+        let settled = async {
+            let mut savepoint = transaction.begin().await?;
+            settle_task(&mut savepoint, task, outcome, settings).await?;
+            savepoint.commit().await
+        }
+        .await;
+        if let Err(e) = settled {
+            tracing::error!(
+                error.message = %e,
+                subscriber_email = %task.subscriber_email,
+                "Failed to update a delivery task",
+            );
+        }
+    }
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+fn unsubscribe_link(base_url: &str, subscriber_id: Uuid, hmac_secret: &Secret<String>) -> String {
+    format!(
+        "{}/subscriptions/unsubscribe?token={}",
+        base_url,
+        UnsubscribeToken::sign(subscriber_id, hmac_secret).as_ref()
+    )
+}
+
+fn open_tracking_link(
+    base_url: &str,
+    subscriber_id: Uuid,
+    newsletter_issue_id: Uuid,
+    hmac_secret: &Secret<String>,
+) -> String {
+    format!(
+        "{}/t/o/{}.gif",
+        base_url,
+        OpenToken::sign(subscriber_id, newsletter_issue_id, hmac_secret).as_ref()
+    )
+}
+
+/// The links of an issue that are specific to one subscriber.
+struct DeliveryLinks<'a> {
+    unsubscribe: String,
+    click_tracker: Option<ClickTracker<'a>>,
+    /// The source of a tracking image, added at the end of the HTML body.
+    open_tracking: Option<String>,
+}
+
+/// An issue rendered for one subscriber.
+///
+/// Every issue carries an unsubscribe link in its body and, for mail clients that offer their
+/// own button, in `List-Unsubscribe` headers with one-click support (RFC 8058). With click
+/// tracking, the links of the issue itself are rewritten, not the ones the template adds.
+struct IssueDelivery<'a> {
+    task_index: usize,
+    recipient: SubscriberEmail,
+    subscriber_id: Uuid,
+    title: &'a str,
+    html_content: String,
+    text_content: String,
+    list_unsubscribe: String,
+}
+
+impl<'a> IssueDelivery<'a> {
+    fn render(
+        email_templates: &EmailTemplates,
+        task_index: usize,
+        recipient: SubscriberEmail,
+        subscriber: &ConfirmedSubscriber,
+        issue: &'a NewsletterIssue,
+        links: DeliveryLinks<'_>,
+    ) -> Result<Self, TemplateError> {
+        let (html_content, text_content) = match links.click_tracker {
+            Some(tracker) => (
+                Cow::Owned(tracker.track_html(&issue.html_content)),
+                Cow::Owned(tracker.track_text(&issue.text_content)),
+            ),
+            None => (
+                Cow::Borrowed(issue.html_content.as_str()),
+                Cow::Borrowed(issue.text_content.as_str()),
+            ),
+        };
+        let mut email = email_templates.render(&NewsletterEmail {
+            subscriber_name: &subscriber.name,
+            title: &issue.title,
+            html_content: &html_content,
+            text_content: &text_content,
+            unsubscribe_link: &links.unsubscribe,
+        })?;
+        if let Some(source) = links.open_tracking {
+            let image = format!(
+                r#"<img src="{}" width="1" height="1" alt="" style="display: block; border: 0;">"#,
+                source
+            );
+            email.html = match email.html.rfind("</body>") {
+                Some(end) => format!("{}{}{}", &email.html[..end], image, &email.html[end..]),
+                None => email.html + &image,
+            };
+        }
+
+        Ok(Self {
+            task_index,
+            recipient,
+            subscriber_id: subscriber.id,
+            title: &issue.title,
+            html_content: email.html,
+            text_content: email.text,
+            list_unsubscribe: format!("<{}>", links.unsubscribe),
+        })
+    }
+
+    fn headers(&self) -> [EmailHeader<'_>; 2] {
+        [
+            EmailHeader {
+                name: "List-Unsubscribe",
+                value: &self.list_unsubscribe,
+            },
+            EmailHeader {
+                name: "List-Unsubscribe-Post",
+                value: "List-Unsubscribe=One-Click",
+            },
+        ]
+    }
+
+    fn email<'b>(&'b self, headers: &'b [EmailHeader<'b>]) -> OutgoingEmail<'b> {
+        OutgoingEmail {
+            recipient: &self.recipient,
+            subject: self.title,
+            html_content: &self.html_content,
+            text_content: &self.text_content,
+            headers,
+        }
+    }
+}
+
+// Exponential backoff with "equal jitter": half of the delay is fixed, the other half random,
+// so that deliveries failing together during a provider brownout do not retry in lockstep.
+fn backoff_delay(settings: &IssueDeliverySettings, n_retries: i32) -> chrono::Duration {
+    let exponent = n_retries.clamp(0, 31) as u32;
+    let delay = settings
+        .base_backoff_seconds
+        .saturating_mul(2u64.saturating_pow(exponent))
+        .min(settings.max_backoff_seconds)
+        .max(1);
+    let jitter = thread_rng().gen_range(0..=delay / 2);
+
+    chrono::Duration::seconds((delay - delay / 2 + jitter) as i64)
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i32,
+}
+
+/// Locks up to `batch_size` due tasks. They stay locked, and invisible to other workers,
+/// until the returned transaction ends.
+#[tracing::instrument(skip_all)]
+async fn dequeue_tasks(
+    connection_pool: &PgPool,
+    batch_size: i64,
+) -> Result<(PgTransaction, Vec<DeliveryTask>), sqlx::Error> {
+    let mut transaction = connection_pool.begin().await?;
+    let tasks = sqlx::query_as!(
+        DeliveryTask,
+        r#"SELECT newsletter_issue_id, subscriber_email, n_retries
+        FROM issue_delivery_queue
+        WHERE next_attempt_at <= now()
+        ORDER BY next_attempt_at
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT $1"#,
+        batch_size
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    Ok((transaction, tasks))
+}
+
+/// Removes a delivered task from the queue, or schedules its next attempt, or moves it to the
+/// dead letters once it has run out of them.
+#[tracing::instrument(skip_all)]
+async fn settle_task(
+    transaction: &mut Transaction<'_, Postgres>,
+    task: &DeliveryTask,
+    outcome: Result<(), DeliveryFailure>,
+    settings: &IssueDeliverySettings,
+) -> Result<(), sqlx::Error> {
+    match outcome {
+        Ok(()) => delete_task(transaction, task).await,
+        Err(DeliveryFailure::Transient(error)) if task.n_retries + 1 < settings.max_retries => {
+            let n_retries = task.n_retries + 1;
+            let next_attempt_at = Utc::now() + backoff_delay(settings, n_retries);
+            tracing::warn!(
+                error.message = %error,
+                subscriber_email = %task.subscriber_email,
+                n_retries,
+                %next_attempt_at,
+                "Failed to deliver issue to a confirmed subscriber. Retrying later",
+            );
+            reschedule_task(transaction, task, n_retries, next_attempt_at, &error).await
+        }
+        Err(DeliveryFailure::Transient(error)) | Err(DeliveryFailure::Permanent(error)) => {
+            tracing::error!(
+                error.message = %error,
+                subscriber_email = %task.subscriber_email,
+                "Failed to deliver issue to a confirmed subscriber. Moving it to dead letters",
+            );
+            dead_letter_task(transaction, task, &error).await
+        }
+    }
+}
+
+/// Keeps track of what was sent for the engagement stats of the issue. A requeued dead letter
+/// that gets sent replaces the earlier record.
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut Transaction<'_, Postgres>,
+    task: &DeliveryTask,
+    subscriber_id: Uuid,
+    sent_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO issue_deliveries (newsletter_issue_id, subscriber_id, subscriber_email, sent_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (newsletter_issue_id, subscriber_id) DO UPDATE SET sent_at = EXCLUDED.sent_at"#,
+        task.newsletter_issue_id,
+        subscriber_id,
+        task.subscriber_email,
+        sent_at
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    transaction: &mut Transaction<'_, Postgres>,
+    task: &DeliveryTask,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2"#,
+        task.newsletter_issue_id,
+        task.subscriber_email
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    transaction: &mut Transaction<'_, Postgres>,
+    task: &DeliveryTask,
+    n_retries: i32,
+    next_attempt_at: DateTime<Utc>,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE issue_delivery_queue
+        SET n_retries = $3, next_attempt_at = $4, last_error = $5
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2"#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        n_retries,
+        next_attempt_at,
+        error
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn dead_letter_task(
+    transaction: &mut Transaction<'_, Postgres>,
+    task: &DeliveryTask,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO issue_delivery_dead_letters(
+            newsletter_issue_id, subscriber_email, n_retries, last_error, failed_at
+        )
+        VALUES($1, $2, $3, $4, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET n_retries = EXCLUDED.n_retries,
+            last_error = EXCLUDED.last_error,
+            failed_at = EXCLUDED.failed_at"#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        // Counts the attempt that just failed, like `reschedule_task` does.
+        task.n_retries + 1,
+        error
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2"#,
+        task.newsletter_issue_id,
+        task.subscriber_email
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    name: String,
+}
+
+/// The subscribers of `tasks` that are still confirmed, by email.
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscribers(
+    connection_pool: &PgPool,
+    tasks: &[DeliveryTask],
+) -> Result<HashMap<String, ConfirmedSubscriber>, sqlx::Error> {
+    let emails: Vec<String> = tasks
+        .iter()
+        .map(|task| task.subscriber_email.clone())
+        .collect();
+    let rows = sqlx::query!(
+        r#"SELECT id, email, name FROM subscriptions WHERE email = ANY($1) AND status = $2"#,
+        &emails,
+        SubscriptionStatus::Confirmed.as_str()
+    )
+    .fetch_all(connection_pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let subscriber = ConfirmedSubscriber {
+                id: row.id,
+                name: row.name,
+            };
+            (row.email, subscriber)
+        })
+        .collect())
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+    track_clicks: bool,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issue(
+    connection_pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<NewsletterIssue, sqlx::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"SELECT title, text_content, html_content, track_clicks
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1"#,
+        issue_id
+    )
+    .fetch_one(connection_pool)
+    .await?;
+
+    Ok(issue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backoff_delay;
+    use crate::configuration::IssueDeliverySettings;
+
+    fn settings() -> IssueDeliverySettings {
+        IssueDeliverySettings {
+            max_retries: 10,
+            batch_size: 100,
+            base_backoff_seconds: 30,
+            max_backoff_seconds: 3600,
+        }
+    }
+
+    #[test]
+    fn backoff_delay_doubles_with_each_retry() {
+        for n_retries in 0..5 {
+            let full_delay = 30 * 2i64.pow(n_retries as u32);
+            let delay = backoff_delay(&settings(), n_retries).num_seconds();
+
+            assert!(delay >= full_delay / 2, "{} < {}", delay, full_delay / 2);
+            assert!(delay <= full_delay, "{} > {}", delay, full_delay);
+        }
+    }
+
+    #[test]
+    fn backoff_delay_is_capped_at_the_maximum() {
+        for n_retries in [10, 31, 1000] {
+            let delay = backoff_delay(&settings(), n_retries).num_seconds();
+
+            assert!(delay >= 1800);
+            assert!(delay <= 3600);
+        }
+    }
+}
@@ -1,6 +1,16 @@
+pub mod authentication;
+pub mod click_tracking;
+pub mod clock;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_templates;
+pub mod idempotency;
+pub mod issue_delivery_worker;
 pub mod routes;
+pub mod session_state;
+pub mod session_store;
 pub mod startup;
+pub mod suppression_list;
 pub mod telemetry;
+pub mod utils;
@@ -1,6 +1,10 @@
-use newsletter_api::startup::Application;
+use newsletter_api::authentication::{create_user, NewPassword};
+use newsletter_api::configuration::Settings;
+use newsletter_api::domain::SubscriberEmail;
+use newsletter_api::startup::{get_connection_pool, Application};
 use newsletter_api::telemetry::init_tracing_subscriber;
 use newsletter_api::{configuration, telemetry::get_tracing_subscriber};
+use secrecy::Secret;
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
@@ -12,7 +16,44 @@ async fn main() -> Result<(), std::io::Error> {
     init_tracing_subscriber(subscriber);
 
     let settings = configuration::get_configuration().expect("Failed to read the configuration");
+
+    let args: Vec<String> = std::env::args().collect();
+    match args.as_slice() {
+        [_, command, username] if command == "create-admin" => {
+            return create_admin(settings, username, None).await;
+        }
+        [_, command, username, email] if command == "create-admin" => {
+            return create_admin(settings, username, Some(email)).await;
+        }
+        _ => {}
+    }
+
     let application = Application::build(settings).await?;
     application.run_until_stoped().await?;
     Ok(())
 }
+
+// Usage: `echo "$PASSWORD" | newsletter-api create-admin <username> [email]`
+async fn create_admin(
+    settings: Settings,
+    username: &str,
+    email: Option<&String>,
+) -> Result<(), std::io::Error> {
+    let email = email
+        .map(|email| SubscriberEmail::parse(email.clone()))
+        .transpose()
+        .map_err(|e| std::io::Error::other(format!("invalid email: {}", e)))?;
+
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    let password = NewPassword::parse(Secret::new(password)).map_err(std::io::Error::other)?;
+
+    let connection_pool = get_connection_pool(&settings);
+    let user_id = create_user(username, email.as_ref(), password, &connection_pool)
+        .await
+        .map_err(|e| std::io::Error::other(format!("{:?}", e)))?;
+    println!("Created admin user {} with id {}", username, user_id);
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{create_api_key, list_api_keys, revoke_api_key, ApiScope, UserId};
+use crate::routes::error_chain_fmt;
+
+#[derive(serde::Deserialize)]
+pub struct NewApiKeyData {
+    name: String,
+    scopes: Vec<ApiScope>,
+}
+
+#[derive(serde::Serialize)]
+struct CreatedApiKey<'a> {
+    api_key_id: Uuid,
+    name: &'a str,
+    key: &'a str,
+    scopes: &'a [ApiScope],
+}
+
+#[tracing::instrument(name = "Issue API key", skip(body, connection), fields(user_id = %*user_id))]
+pub async fn issue_api_key(
+    body: web::Json<NewApiKeyData>,
+    connection: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ApiKeyAdminError> {
+    let name = body.name.trim();
+    if name.is_empty() {
+        return Err(ApiKeyAdminError::Validation("the key needs a name".into()));
+    }
+    if body.scopes.is_empty() {
+        return Err(ApiKeyAdminError::Validation(
+            "the key needs at least one scope".into(),
+        ));
+    }
+
+    let new_api_key = create_api_key(**user_id, name, &body.scopes, &connection).await?;
+
+    // This is the only time the full key is ever returned.
+    Ok(HttpResponse::Created().json(CreatedApiKey {
+        api_key_id: new_api_key.api_key_id,
+        name,
+        key: new_api_key.key.expose_secret(),
+        scopes: &body.scopes,
+    }))
+}
+
+#[tracing::instrument(name = "List API keys", skip(connection), fields(user_id = %*user_id))]
+pub async fn get_api_keys(
+    connection: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ApiKeyAdminError> {
+    let api_keys = list_api_keys(**user_id, &connection).await?;
+
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+#[tracing::instrument(name = "Revoke API key", skip(connection), fields(user_id = %*user_id))]
+pub async fn revoke_key(
+    api_key_id: web::Path<Uuid>,
+    connection: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, ApiKeyAdminError> {
+    if !revoke_api_key(**user_id, api_key_id.into_inner(), &connection).await? {
+        return Err(ApiKeyAdminError::NotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(thiserror::Error)]
+pub enum ApiKeyAdminError {
+    #[error("{0}")]
+    Validation(String),
+    #[error("no such API key")]
+    NotFound,
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl std::fmt::Debug for ApiKeyAdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for ApiKeyAdminError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiKeyAdminError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiKeyAdminError::NotFound => StatusCode::NOT_FOUND,
+            ApiKeyAdminError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self.status_code() {
+            StatusCode::INTERNAL_SERVER_ERROR => {
+                tracing::error!("{:?}", self);
+                HttpResponse::InternalServerError().finish()
+            }
+            status_code => HttpResponse::build(status_code).body(self.to_string()),
+        }
+    }
+}
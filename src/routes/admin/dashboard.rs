@@ -0,0 +1,49 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::utils::e500;
+
+pub async fn admin_dashboard(
+    user_id: web::ReqData<UserId>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let username = get_username(*user_id, &connection).await.map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Admin dashboard</title>
+</head>
+<body>
+    <p>Welcome {username}!</p>
+    <p>Available actions:</p>
+    <ol>
+        <li><a href="/admin/password">Change password</a></li>
+        <li><a href="/admin/two_factor">Two-factor authentication</a></li>
+        <li>
+            <form name="logoutForm" action="/admin/logout" method="post">
+                <input type="submit" value="Logout">
+            </form>
+        </li>
+    </ol>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Get username", skip(connection_pool))]
+pub async fn get_username(user_id: Uuid, connection_pool: &PgPool) -> Result<String, sqlx::Error> {
+    let row = sqlx::query!(r#"SELECT username FROM users WHERE user_id = $1"#, user_id,)
+        .fetch_one(connection_pool)
+        .await?;
+
+    Ok(row.username)
+}
@@ -0,0 +1,97 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::routes::error_chain_fmt;
+
+#[derive(serde::Serialize)]
+pub struct DeadLetter {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i32,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RequeueData {
+    newsletter_issue_id: Uuid,
+    subscriber_email: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct RequeueResponse {
+    requeued: u64,
+}
+
+#[tracing::instrument(name = "List dead letters", skip(connection))]
+pub async fn list_dead_letters(
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, DeadLetterError> {
+    let dead_letters = sqlx::query_as!(
+        DeadLetter,
+        r#"SELECT newsletter_issue_id, subscriber_email, n_retries, last_error, failed_at
+        FROM issue_delivery_dead_letters
+        ORDER BY failed_at DESC"#
+    )
+    .fetch_all(connection.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(dead_letters))
+}
+
+#[tracing::instrument(
+    name = "Requeue dead letters",
+    skip(body, connection),
+    fields(newsletter_issue_id = %body.newsletter_issue_id)
+)]
+pub async fn requeue_dead_letters(
+    body: web::Json<RequeueData>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, DeadLetterError> {
+    let mut transaction = connection.begin().await?;
+    let requeued = sqlx::query!(
+        r#"WITH requeued AS (
+            DELETE FROM issue_delivery_dead_letters
+            WHERE newsletter_issue_id = $1
+                AND ($2::TEXT IS NULL OR subscriber_email = $2)
+            RETURNING newsletter_issue_id, subscriber_email
+        )
+        INSERT INTO issue_delivery_queue(newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, subscriber_email FROM requeued
+        ON CONFLICT DO NOTHING"#,
+        body.newsletter_issue_id,
+        body.subscriber_email
+    )
+    .execute(&mut transaction)
+    .await?
+    .rows_affected();
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(RequeueResponse { requeued }))
+}
+
+#[derive(thiserror::Error)]
+pub enum DeadLetterError {
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl std::fmt::Debug for DeadLetterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for DeadLetterError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        tracing::error!("{:?}", self);
+        HttpResponse::new(self.status_code())
+    }
+}
@@ -0,0 +1,17 @@
+mod api_keys;
+mod dashboard;
+mod dead_letters;
+mod logout;
+mod newsletter_issues;
+mod password;
+mod suppressions;
+mod two_factor;
+
+pub use api_keys::*;
+pub use dashboard::*;
+pub use dead_letters::*;
+pub use logout::*;
+pub use newsletter_issues::*;
+pub use password::*;
+pub use suppressions::*;
+pub use two_factor::*;
@@ -0,0 +1,150 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::SubscriptionStatus;
+use crate::utils::e500;
+
+/// Engagement of the recipients of an issue. Each count is a number of recipients.
+#[derive(serde::Serialize)]
+pub struct IssueStats {
+    sent: i64,
+    delivered: i64,
+    bounced: i64,
+    opened: i64,
+    clicked: i64,
+    unsubscribed: i64,
+    /// Each count as a fraction of `sent`.
+    rates: EngagementRates,
+}
+
+#[derive(serde::Serialize)]
+pub struct EngagementRates {
+    delivered: f64,
+    bounced: f64,
+    opened: f64,
+    clicked: f64,
+    unsubscribed: f64,
+}
+
+/// How often one link of an issue was clicked, and by how many subscribers.
+#[derive(serde::Serialize)]
+pub struct LinkClicks {
+    url: String,
+    clicks: i64,
+    subscribers: i64,
+}
+
+/// The tracked links of an issue, most clicked first. Empty unless the issue was published
+/// with `track_clicks`.
+#[tracing::instrument(name = "List issue clicks", skip(connection))]
+pub async fn list_issue_clicks(
+    newsletter_issue_id: web::Path<Uuid>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let clicks = sqlx::query_as!(
+        LinkClicks,
+        r#"SELECT url, COUNT(*) AS "clicks!", COUNT(DISTINCT subscriber_id) AS "subscribers!"
+        FROM link_clicks
+        WHERE newsletter_issue_id = $1
+        GROUP BY url
+        ORDER BY 2 DESC, url"#,
+        newsletter_issue_id.into_inner()
+    )
+    .fetch_all(connection.get_ref())
+    .await
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(clicks))
+}
+
+/// Delivery and bounce notifications, and unsubscriptions, do not say which email caused them.
+/// They are counted for the most recent issue sent to the address before they happened.
+#[tracing::instrument(name = "Get issue stats", skip(connection))]
+pub async fn get_issue_stats(
+    newsletter_issue_id: web::Path<Uuid>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let issue_exists = sqlx::query!(
+        r#"SELECT 1 AS "exists" FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_optional(connection.get_ref())
+    .await
+    .map_err(e500)?
+    .is_some();
+    if !issue_exists {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let counts = sqlx::query!(
+        r#"WITH deliveries AS (
+            SELECT d.subscriber_id, lower(d.subscriber_email) AS email, d.sent_at,
+                (SELECT MIN(later.sent_at) FROM issue_deliveries later
+                WHERE later.subscriber_id = d.subscriber_id AND later.sent_at > d.sent_at)
+                    AS next_sent_at
+            FROM issue_deliveries d
+            WHERE d.newsletter_issue_id = $1
+        )
+        SELECT
+            COUNT(*) AS "sent!",
+            COUNT(*) FILTER (WHERE EXISTS (
+                SELECT 1 FROM email_events e
+                WHERE e.event_type = 'delivery' AND lower(e.email) = d.email
+                    AND e.received_at >= d.sent_at
+                    AND (d.next_sent_at IS NULL OR e.received_at < d.next_sent_at)
+            )) AS "delivered!",
+            COUNT(*) FILTER (WHERE EXISTS (
+                SELECT 1 FROM email_events e
+                WHERE e.event_type = 'bounce' AND lower(e.email) = d.email
+                    AND e.received_at >= d.sent_at
+                    AND (d.next_sent_at IS NULL OR e.received_at < d.next_sent_at)
+            )) AS "bounced!",
+            COUNT(*) FILTER (WHERE EXISTS (
+                SELECT 1 FROM email_opens o
+                WHERE o.newsletter_issue_id = $1 AND o.subscriber_id = d.subscriber_id
+            )) AS "opened!",
+            COUNT(*) FILTER (WHERE EXISTS (
+                SELECT 1 FROM link_clicks c
+                WHERE c.newsletter_issue_id = $1 AND c.subscriber_id = d.subscriber_id
+            )) AS "clicked!",
+            COUNT(*) FILTER (WHERE EXISTS (
+                SELECT 1 FROM subscription_status_transitions t
+                WHERE t.subscriber_id = d.subscriber_id AND t.to_status = $2
+                    AND t.transitioned_at >= d.sent_at
+                    AND (d.next_sent_at IS NULL OR t.transitioned_at < d.next_sent_at)
+            )) AS "unsubscribed!"
+        FROM deliveries d"#,
+        newsletter_issue_id,
+        SubscriptionStatus::Unsubscribed.as_str()
+    )
+    .fetch_one(connection.get_ref())
+    .await
+    .map_err(e500)?;
+
+    let rate = |count: i64| {
+        if counts.sent == 0 {
+            0.0
+        } else {
+            count as f64 / counts.sent as f64
+        }
+    };
+    let stats = IssueStats {
+        rates: EngagementRates {
+            delivered: rate(counts.delivered),
+            bounced: rate(counts.bounced),
+            opened: rate(counts.opened),
+            clicked: rate(counts.clicked),
+            unsubscribed: rate(counts.unsubscribed),
+        },
+        sent: counts.sent,
+        delivered: counts.delivered,
+        bounced: counts.bounced,
+        opened: counts.opened,
+        clicked: counts.clicked,
+        unsubscribed: counts.unsubscribed,
+    };
+
+    Ok(HttpResponse::Ok().json(stats))
+}
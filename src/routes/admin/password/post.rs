@@ -0,0 +1,63 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::authentication::{validate_credentials, AuthError, Credentials, NewPassword, UserId};
+use crate::routes::get_username;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    current_password: Secret<String>,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(name = "Change password", skip(form, connection), fields(user_id = %*user_id))]
+pub async fn change_password(
+    form: web::Form<FormData>,
+    connection: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let form = form.0;
+
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        FlashMessage::error(
+            "You entered two different new passwords - the field values must match.",
+        )
+        .send();
+        return Ok(see_other("/admin/password"));
+    }
+
+    let new_password = match NewPassword::parse(form.new_password) {
+        Ok(new_password) => new_password,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return Ok(see_other("/admin/password"));
+        }
+    };
+
+    let username = get_username(*user_id, &connection).await.map_err(e500)?;
+    let credentials = Credentials {
+        username,
+        password: form.current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &connection).await {
+        return match e {
+            AuthError::InvalidCredentials => {
+                FlashMessage::error("The current password is incorrect.").send();
+                Ok(see_other("/admin/password"))
+            }
+            _ => Err(e500(e)),
+        };
+    }
+
+    crate::authentication::change_password(*user_id, new_password, &connection)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Your password has been changed.").send();
+
+    Ok(see_other("/admin/password"))
+}
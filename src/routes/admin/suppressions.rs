@@ -0,0 +1,128 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use sqlx::PgPool;
+
+use crate::domain::{SubscriberEmail, SubscriptionStatus};
+use crate::routes::error_chain_fmt;
+use crate::routes::subscriptions::SubscriptionStatusError;
+use crate::suppression_list::{
+    list_suppressions, remove_suppression, suppress_subscriber, SuppressionSource,
+};
+
+#[derive(serde::Deserialize)]
+pub struct NewSuppressionData {
+    email: String,
+    reason: String,
+    #[serde(default = "default_source")]
+    source: SuppressionSource,
+}
+
+fn default_source() -> SuppressionSource {
+    SuppressionSource::Manual
+}
+
+#[derive(serde::Deserialize)]
+pub struct RemoveSuppressionData {
+    email: String,
+}
+
+#[tracing::instrument(name = "List suppressions", skip(connection))]
+pub async fn get_suppressions(
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, SuppressionAdminError> {
+    let suppressions = list_suppressions(&connection).await?;
+
+    Ok(HttpResponse::Ok().json(suppressions))
+}
+
+/// Suppresses an address by hand. Its subscriber, if any, is marked `suppressed` too.
+#[tracing::instrument(
+    name = "Add suppression",
+    skip(body, connection),
+    fields(email = %body.email, source = ?body.source)
+)]
+pub async fn suppress_address(
+    body: web::Json<NewSuppressionData>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, SuppressionAdminError> {
+    let email = SubscriberEmail::parse(body.email.clone())
+        .map_err(|e| SuppressionAdminError::Validation(e.to_string()))?;
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(SuppressionAdminError::Validation(
+            "the suppression needs a reason".into(),
+        ));
+    }
+
+    let mut transaction = connection.begin().await?;
+    let suppressed = suppress_subscriber(
+        &mut transaction,
+        email.as_ref(),
+        SubscriptionStatus::Suppressed,
+        reason,
+        body.source,
+    )
+    .await?;
+    if !suppressed {
+        return Err(SuppressionAdminError::AlreadySuppressed);
+    }
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+/// Lifts a suppression. The subscriber keeps their status and has to sign up again.
+#[tracing::instrument(name = "Remove suppression", skip(body, connection), fields(email = %body.email))]
+pub async fn unsuppress_address(
+    body: web::Json<RemoveSuppressionData>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, SuppressionAdminError> {
+    if !remove_suppression(&connection, &body.email).await? {
+        return Err(SuppressionAdminError::NotFound);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(thiserror::Error)]
+pub enum SuppressionAdminError {
+    #[error("{0}")]
+    Validation(String),
+    #[error("the address is already suppressed")]
+    AlreadySuppressed,
+    #[error("the address is not suppressed")]
+    NotFound,
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error(transparent)]
+    StatusError(#[from] SubscriptionStatusError),
+}
+
+impl std::fmt::Debug for SuppressionAdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for SuppressionAdminError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SuppressionAdminError::Validation(_) => StatusCode::BAD_REQUEST,
+            SuppressionAdminError::AlreadySuppressed => StatusCode::CONFLICT,
+            SuppressionAdminError::NotFound => StatusCode::NOT_FOUND,
+            SuppressionAdminError::DatabaseError(_) | SuppressionAdminError::StatusError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self.status_code() {
+            StatusCode::INTERNAL_SERVER_ERROR => {
+                tracing::error!("{:?}", self);
+                HttpResponse::InternalServerError().finish()
+            }
+            status_code => HttpResponse::build(status_code).body(self.to_string()),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{
+    confirm_totp_enrollment, get_two_factor_status, is_two_factor_required, provisioning_uri,
+    set_two_factor_required, start_totp_enrollment, verify_second_factor, TwoFactorStatus, UserId,
+};
+use crate::clock::Clock;
+use crate::routes::get_username;
+use crate::utils::{e500, see_other};
+
+pub async fn two_factor_settings(
+    user_id: web::ReqData<UserId>,
+    connection: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let mut messages_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", message.content()).unwrap();
+    }
+
+    let status_html = match get_two_factor_status(*user_id, &connection)
+        .await
+        .map_err(e500)?
+    {
+        TwoFactorStatus::Disabled => r#"<p>Two-factor authentication is disabled.</p>
+    <form action="/admin/two_factor/enroll" method="post">
+        <button type="submit">Set up an authenticator app</button>
+    </form>"#
+            .to_string(),
+        TwoFactorStatus::Pending(secret) => {
+            let username = get_username(*user_id, &connection).await.map_err(e500)?;
+            format!(
+                r#"<p>Scan this URI as a QR code with your authenticator app, or type the secret in by hand.</p>
+    <p><code>{uri}</code></p>
+    <p>Secret: <code>{secret}</code></p>
+    <form action="/admin/two_factor/confirm" method="post">
+        <label>Code from the app
+            <input type="text" inputmode="numeric" autocomplete="one-time-code" name="code">
+        </label>
+        <button type="submit">Enable two-factor authentication</button>
+    </form>"#,
+                uri = provisioning_uri(&secret, &username),
+                secret = secret.expose_secret(),
+            )
+        }
+        TwoFactorStatus::Enabled => r#"<p>Two-factor authentication is enabled.</p>
+    <form action="/admin/two_factor/disable" method="post">
+        <label>Code from the app or a recovery code
+            <input type="text" name="code">
+        </label>
+        <button type="submit">Disable two-factor authentication</button>
+    </form>"#
+            .to_string(),
+    };
+
+    let required = is_two_factor_required(&connection).await.map_err(e500)?;
+    let checked = if required { " checked" } else { "" };
+    let code_html = if required {
+        r#"<label>Code from the app or a recovery code, to make it optional
+            <input type="text" name="code">
+        </label>"#
+    } else {
+        ""
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {messages_html}
+    {status_html}
+    <h2>Policy</h2>
+    <form action="/admin/two_factor/policy" method="post">
+        <label>
+            <input type="checkbox" name="require_two_factor" value="on"{checked}>
+            Require two-factor authentication for every admin
+        </label>
+        {code_html}
+        <button type="submit">Save</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Enroll in two-factor authentication", skip(connection), fields(user_id = %*user_id))]
+pub async fn enroll_two_factor(
+    user_id: web::ReqData<UserId>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    start_totp_enrollment(**user_id, &connection)
+        .await
+        .map_err(e500)?;
+
+    Ok(see_other("/admin/two_factor"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct CodeFormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "Confirm two-factor enrollment",
+    skip(form, connection, clock),
+    fields(user_id = %*user_id)
+)]
+pub async fn confirm_two_factor(
+    form: web::Form<CodeFormData>,
+    user_id: web::ReqData<UserId>,
+    connection: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(recovery_codes) =
+        confirm_totp_enrollment(**user_id, &form.code, clock.now(), &connection)
+            .await
+            .map_err(e500)?
+    else {
+        FlashMessage::error("The authentication code is invalid.").send();
+        return Ok(see_other("/admin/two_factor"));
+    };
+
+    let mut codes_html = String::new();
+    for code in &recovery_codes {
+        writeln!(codes_html, "<li><code>{}</code></li>", code.expose_secret()).unwrap();
+    }
+
+    // The recovery codes are only stored hashed, so this page is the only chance to see them.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Recovery codes</title>
+</head>
+<body>
+    <p>Two-factor authentication is enabled.</p>
+    <p>Store these recovery codes somewhere safe. Each one can be used once instead of a code
+    from your app, and they will not be shown again.</p>
+    <ol>
+        {codes_html}
+    </ol>
+    <p><a href="/admin/dashboard">Continue to the dashboard</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(
+    name = "Disable two-factor authentication",
+    skip(form, connection, clock),
+    fields(user_id = %*user_id)
+)]
+pub async fn disable_two_factor(
+    form: web::Form<CodeFormData>,
+    user_id: web::ReqData<UserId>,
+    connection: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if is_two_factor_required(&connection).await.map_err(e500)? {
+        FlashMessage::error("Two-factor authentication is required for every admin.").send();
+        return Ok(see_other("/admin/two_factor"));
+    }
+    if !verify_second_factor(**user_id, &form.code, clock.now(), &connection)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("The authentication code is invalid.").send();
+        return Ok(see_other("/admin/two_factor"));
+    }
+
+    crate::authentication::disable_two_factor(**user_id, &connection)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Two-factor authentication has been disabled.").send();
+
+    Ok(see_other("/admin/two_factor"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PolicyFormData {
+    // HTML checkboxes are only submitted when ticked.
+    require_two_factor: Option<String>,
+    /// A code from the app or a recovery code, needed to switch the policy off.
+    code: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "Update two-factor policy",
+    skip(form, connection, clock),
+    fields(user_id = %*user_id)
+)]
+pub async fn update_two_factor_policy(
+    form: web::Form<PolicyFormData>,
+    user_id: web::ReqData<UserId>,
+    connection: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let required = form.require_two_factor.is_some();
+    // Whoever can reach this page while the policy is on has enrolled, so a stolen session
+    // alone is not enough to weaken it.
+    if !required && is_two_factor_required(&connection).await.map_err(e500)? {
+        let code = form.code.as_deref().unwrap_or_default();
+        if !verify_second_factor(**user_id, code, clock.now(), &connection)
+            .await
+            .map_err(e500)?
+        {
+            FlashMessage::error("The authentication code is invalid.").send();
+            return Ok(see_other("/admin/two_factor"));
+        }
+    }
+
+    set_two_factor_required(required, &connection)
+        .await
+        .map_err(e500)?;
+
+    if required {
+        FlashMessage::info("Two-factor authentication is now required for every admin.").send();
+    } else {
+        FlashMessage::info("Two-factor authentication is now optional.").send();
+    }
+
+    Ok(see_other("/admin/two_factor"))
+}
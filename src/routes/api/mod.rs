@@ -0,0 +1,3 @@
+mod subscribers;
+
+pub use subscribers::*;
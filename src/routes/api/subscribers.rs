@@ -0,0 +1,67 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{ApiKey, ApiScope};
+use crate::domain::NewSubscriber;
+use crate::email_client::EmailSender;
+use crate::email_templates::EmailTemplates;
+use crate::routes::subscriptions::{register_subscriber, FormData};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+
+#[derive(serde::Serialize)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "List subscribers", skip_all, fields(api_key_id = %api_key.api_key_id))]
+pub async fn list_subscribers(
+    api_key: ApiKey,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    api_key.require_scope(ApiScope::SubscribersRead)?;
+
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        ORDER BY subscribed_at"#
+    )
+    .fetch_all(connection.get_ref())
+    .await
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(subscribers))
+}
+
+/// Registers a subscriber on behalf of an API client. The subscriber still has to confirm
+/// through the emailed link, exactly as with the public form.
+#[tracing::instrument(name = "Add subscriber", skip_all, fields(api_key_id = %api_key.api_key_id))]
+pub async fn add_subscriber(
+    api_key: ApiKey,
+    body: web::Json<FormData>,
+    connection: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    email_templates: web::Data<EmailTemplates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    api_key.require_scope(ApiScope::SubscribersWrite)?;
+
+    let new_subscriber: NewSubscriber = body.0.try_into()?;
+    register_subscriber(
+        new_subscriber,
+        &connection,
+        email_client.as_ref(),
+        &email_templates,
+        &base_url.0,
+    )
+    .await?;
+
+    Ok(HttpResponse::Accepted().finish())
+}
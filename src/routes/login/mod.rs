@@ -0,0 +1,7 @@
+mod get;
+mod post;
+mod two_factor;
+
+pub use get::login_form;
+pub use post::login;
+pub use two_factor::{two_factor_form, verify_two_factor};
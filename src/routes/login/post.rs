@@ -0,0 +1,97 @@
+use actix_web::error::InternalError;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::Secret;
+use sqlx::PgPool;
+
+use crate::authentication::{
+    get_two_factor_status, is_two_factor_required, validate_credentials, AuthError, Credentials,
+    TwoFactorStatus,
+};
+use crate::routes::error_chain_fmt;
+use crate::session_state::TypedSession;
+use crate::utils::see_other;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    username: String,
+    password: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Log in",
+    skip(form, connection, session),
+    fields(username = tracing::field::Empty, user_id = tracing::field::Empty)
+)]
+pub async fn login(
+    form: web::Form<FormData>,
+    connection: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let credentials = Credentials {
+        username: form.0.username,
+        password: form.0.password,
+    };
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+
+    match validate_credentials(credentials, &connection).await {
+        Ok(user_id) => {
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let unexpected = |e: anyhow::Error| login_redirect(LoginError::UnexpectedError(e));
+            let two_factor_status = get_two_factor_status(user_id, &connection)
+                .await
+                .map_err(|e| unexpected(e.into()))?;
+            session.renew();
+
+            if let TwoFactorStatus::Enabled = two_factor_status {
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| unexpected(e.into()))?;
+                return Ok(see_other("/login/two_factor"));
+            }
+
+            session
+                .insert_user_id(user_id)
+                .map_err(|e| unexpected(e.into()))?;
+            if is_two_factor_required(&connection)
+                .await
+                .map_err(|e| unexpected(e.into()))?
+            {
+                FlashMessage::info("Two-factor authentication is required. Please enroll now.")
+                    .send();
+                return Ok(see_other("/admin/two_factor"));
+            }
+
+            Ok(see_other("/admin/dashboard"))
+        }
+        Err(e) => {
+            let e = match e {
+                AuthError::InvalidCredentials | AuthError::MissingCredentials(_) => {
+                    LoginError::AuthError(e)
+                }
+                _ => LoginError::UnexpectedError(e.into()),
+            };
+
+            Err(login_redirect(e))
+        }
+    }
+}
+
+fn login_redirect(e: LoginError) -> InternalError<LoginError> {
+    FlashMessage::error(e.to_string()).send();
+    InternalError::from_response(e, see_other("/login"))
+}
+
+#[derive(thiserror::Error)]
+pub enum LoginError {
+    #[error("Authentication failed")]
+    AuthError(#[source] AuthError),
+    #[error("Something went wrong")]
+    UnexpectedError(#[source] anyhow::Error),
+}
+
+impl std::fmt::Debug for LoginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
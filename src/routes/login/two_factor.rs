@@ -0,0 +1,89 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::verify_second_factor;
+use crate::clock::Clock;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+
+pub async fn two_factor_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+
+    let mut messages_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", message.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {messages_html}
+    <form action="/login/two_factor" method="post">
+        <label>Authentication code
+            <input type="text" inputmode="numeric" autocomplete="one-time-code"
+                placeholder="6-digit code or recovery code" name="code">
+        </label>
+        <button type="submit">Verify</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "Verify two-factor login",
+    skip(form, connection, clock, session),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn verify_two_factor(
+    form: web::Form<FormData>,
+    connection: web::Data<PgPool>,
+    clock: web::Data<dyn Clock>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(user_id) = session.get_pending_user_id().map_err(e500)? else {
+        return Ok(see_other("/login"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    if !verify_second_factor(user_id, &form.code, clock.now(), &connection)
+        .await
+        .map_err(e500)?
+    {
+        if session.record_two_factor_failure().map_err(e500)? >= MAX_FAILED_ATTEMPTS {
+            session.remove_pending_user_id();
+            FlashMessage::error("Too many failed attempts. Please log in again.").send();
+            return Ok(see_other("/login"));
+        }
+        FlashMessage::error("The authentication code is invalid.").send();
+        return Ok(see_other("/login/two_factor"));
+    }
+
+    session.remove_pending_user_id();
+    session.renew();
+    session.insert_user_id(user_id).map_err(e500)?;
+
+    Ok(see_other("/admin/dashboard"))
+}
@@ -0,0 +1,225 @@
+use actix_web::http::header::{HeaderValue, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::authentication::{
+    basic_authentication, is_second_factor_expected, validate_credentials, AuthError,
+};
+use crate::authentication::{bearer_authentication, has_bearer_credentials, ApiKeyError, ApiScope};
+use crate::domain::SubscriptionStatus;
+use crate::email_templates::{render_markdown, RenderedEmail};
+use crate::idempotency::{save_response, try_processing, IdempotencyError, NextAction};
+use crate::idempotency::{IdempotencyKey, IdempotencyKeyError};
+use crate::routes::error_chain_fmt;
+
+#[derive(serde::Deserialize)]
+pub struct BodyData {
+    title: String,
+    content: Content,
+    /// Sends the links of the issue through the click tracking redirect.
+    #[serde(default)]
+    track_clicks: bool,
+}
+
+/// Either Markdown, rendered to both parts of the email, or the HTML and plain text parts.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    Markdown { markdown: String },
+    Html { html: String, text: String },
+}
+
+impl Content {
+    fn render(&self) -> RenderedEmail {
+        match self {
+            Content::Markdown { markdown } => render_markdown(markdown),
+            Content::Html { html, text } => RenderedEmail {
+                html: html.clone(),
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Publishing newsletter issue",
+    skip(request, body, connection),
+    fields(title = %body.title, username = tracing::field::Empty, user_id = tracing::field::Empty)
+)]
+pub async fn publish_newsletter(
+    request: HttpRequest,
+    body: web::Json<BodyData>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, PublishError> {
+    // API keys act on behalf of the admin who issued them, so idempotency keys stay per user
+    // whichever way the request authenticates.
+    let user_id = if has_bearer_credentials(request.headers()) {
+        let api_key = bearer_authentication(request.headers(), &connection).await?;
+        api_key.require_scope(ApiScope::NewslettersPublish)?;
+        api_key.user_id
+    } else {
+        let credentials = basic_authentication(request.headers())?;
+        tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+        let user_id = validate_credentials(credentials, &connection).await?;
+        // A password alone must not be enough to email the whole audience when a second
+        // factor is expected; those users publish from a session or with an API key.
+        if is_second_factor_expected(user_id, &connection).await? {
+            return Err(PublishError::SecondFactorRequired);
+        }
+        user_id
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let idempotency_key: IdempotencyKey = request
+        .headers()
+        .get("Idempotency-Key")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(PublishError::MissingIdempotencyKey)?
+        .to_string()
+        .try_into()?;
+
+    let mut transaction = match try_processing(&connection, &idempotency_key, user_id).await? {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+    };
+
+    let content = body.content.render();
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &body.title,
+        &content.text,
+        &content.html,
+        body.track_clicks,
+    )
+    .await?;
+    enqueue_delivery_tasks(&mut transaction, issue_id).await?;
+    let response = HttpResponse::Accepted().finish();
+    let response = save_response(transaction, &idempotency_key, user_id, response).await?;
+
+    Ok(response)
+}
+
+#[derive(thiserror::Error)]
+pub enum PublishError {
+    #[error("authentication failed")]
+    AuthError(#[from] AuthError),
+    #[error("API key authentication failed")]
+    ApiKeyError(#[from] ApiKeyError),
+    #[error("a password is not enough when two-factor authentication is expected")]
+    SecondFactorRequired,
+    #[error("missing Idempotency-Key header")]
+    MissingIdempotencyKey,
+    #[error("invalid Idempotency-Key header: {0}")]
+    InvalidIdempotencyKey(#[from] IdempotencyKeyError),
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("failed to process the idempotency key")]
+    IdempotencyError(#[from] IdempotencyError),
+}
+
+impl std::fmt::Debug for PublishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PublishError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PublishError::AuthError(AuthError::MissingCredentials(_))
+            | PublishError::AuthError(AuthError::InvalidCredentials)
+            | PublishError::SecondFactorRequired => StatusCode::UNAUTHORIZED,
+            PublishError::ApiKeyError(e) => e.status_code(),
+            PublishError::MissingIdempotencyKey | PublishError::InvalidIdempotencyKey(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            PublishError::AuthError(_)
+            | PublishError::DatabaseError(_)
+            | PublishError::IdempotencyError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let PublishError::ApiKeyError(e) = self {
+            return e.error_response();
+        }
+        if let PublishError::SecondFactorRequired = self {
+            return HttpResponse::Unauthorized()
+                .insert_header((WWW_AUTHENTICATE, r#"Bearer realm="publish""#))
+                .body(self.to_string());
+        }
+
+        match self.status_code() {
+            StatusCode::UNAUTHORIZED => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                response.headers_mut().insert(
+                    WWW_AUTHENTICATE,
+                    HeaderValue::from_static(r#"Basic realm="publish""#),
+                );
+                response
+            }
+            StatusCode::BAD_REQUEST => HttpResponse::BadRequest().body(self.to_string()),
+            status_code => {
+                tracing::error!("{:?}", self);
+                HttpResponse::new(status_code)
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "insert newsletter issue", skip_all)]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+    track_clicks: bool,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO newsletter_issues(
+            newsletter_issue_id, title, text_content, html_content, published_at, track_clicks
+        )
+        VALUES($1, $2, $3, $4, $5, $6)"#,
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content,
+        Utc::now(),
+        track_clicks
+    )
+    .execute(transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(newsletter_issue_id)
+}
+
+#[tracing::instrument(name = "enqueue delivery tasks", skip(transaction))]
+async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO issue_delivery_queue(newsletter_issue_id, subscriber_email)
+        SELECT $1, email
+        FROM subscriptions
+        WHERE status = $2"#,
+        newsletter_issue_id,
+        SubscriptionStatus::Confirmed.as_str()
+    )
+    .execute(transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(())
+}
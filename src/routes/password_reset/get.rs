@@ -0,0 +1,102 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::validate_password_reset_token;
+use crate::utils::e500;
+
+pub async fn password_reset_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let messages_html = flash_messages_html(&flash_messages);
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Reset Password</title>
+</head>
+<body>
+    {messages_html}
+    <form action="/password_reset" method="post">
+        <label>Username
+            <input type="text" placeholder="Enter Username" name="username">
+        </label>
+        <button type="submit">Email me a reset link</button>
+    </form>
+    <p><a href="/login">&lt;- Back to login</a></p>
+</body>
+</html>"#,
+        ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: Secret<String>,
+}
+
+#[tracing::instrument(name = "Password reset form", skip_all)]
+pub async fn password_reset_confirm_form(
+    parameters: web::Query<Parameters>,
+    connection: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = &parameters.token;
+    if !is_well_formed(token)
+        || validate_password_reset_token(token, &connection)
+            .await
+            .map_err(e500)?
+            .is_none()
+    {
+        return Ok(super::post::invalid_token_redirect());
+    }
+
+    let messages_html = flash_messages_html(&flash_messages);
+    let token = token.expose_secret();
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Choose a new password</title>
+</head>
+<body>
+    {messages_html}
+    <form action="/password_reset/confirm" method="post">
+        <input type="hidden" name="token" value="{token}">
+        <label>New password
+            <input type="password" placeholder="Enter new password" name="new_password">
+        </label>
+        <br>
+        <label>Confirm new password
+            <input type="password" placeholder="Type the new password again" name="new_password_check">
+        </label>
+        <br>
+        <button type="submit">Reset password</button>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+/// Reset tokens are alphanumeric, so anything else can be rejected before it is echoed back
+/// into a page or a redirect.
+pub(super) fn is_well_formed(token: &Secret<String>) -> bool {
+    let token = token.expose_secret();
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn flash_messages_html(flash_messages: &IncomingFlashMessages) -> String {
+    let mut messages_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(messages_html, "<p><i>{}</i></p>", message.content()).unwrap();
+    }
+    messages_html
+}
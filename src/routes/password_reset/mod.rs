@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::{password_reset_confirm_form, password_reset_form};
+pub use post::{confirm_password_reset, request_password_reset};
@@ -0,0 +1,176 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::Instrument;
+
+use super::get::is_well_formed;
+use crate::authentication::{
+    create_password_reset_token, get_password_reset_recipient, reset_password, NewPassword,
+    PasswordResetRecipient, PASSWORD_RESET_TOKEN_TTL_MINUTES,
+};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailSender;
+use crate::email_templates::{EmailTemplates, PasswordResetEmail};
+use crate::startup::ApplicationBaseUrl;
+use crate::suppression_list::send_email_unless_suppressed;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    username: String,
+}
+
+#[tracing::instrument(
+    name = "Request password reset",
+    skip(form, connection, email_client, email_templates, base_url),
+    fields(username = %form.username)
+)]
+pub async fn request_password_reset(
+    form: web::Form<RequestFormData>,
+    connection: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    email_templates: web::Data<EmailTemplates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> HttpResponse {
+    // The response is the same whether or not the account exists, so the form cannot be used
+    // to find out which usernames are valid. Looking the account up and emailing it happen in
+    // the background, otherwise the response time would give it away.
+    let reset = email_reset_link_if_registered(
+        form.0.username,
+        connection.get_ref().clone(),
+        email_client.into_inner(),
+        email_templates.into_inner(),
+        base_url.0.clone(),
+    );
+    tokio::spawn(
+        async move {
+            if let Err(e) = reset.await {
+                tracing::error!(error.message = %e, "Failed to send the password reset email");
+            }
+        }
+        .in_current_span(),
+    );
+
+    FlashMessage::info(
+        "If that account has an email address on file, a password reset link is on its way.",
+    )
+    .send();
+
+    see_other("/login")
+}
+
+async fn email_reset_link_if_registered(
+    username: String,
+    connection_pool: PgPool,
+    email_client: Arc<dyn EmailSender>,
+    email_templates: Arc<EmailTemplates>,
+    base_url: String,
+) -> Result<(), anyhow::Error> {
+    let Some(recipient) = get_password_reset_recipient(&username, &connection_pool).await? else {
+        return Ok(());
+    };
+    let token = create_password_reset_token(recipient.user_id, &connection_pool).await?;
+
+    send_password_reset_email(
+        &connection_pool,
+        email_client.as_ref(),
+        &email_templates,
+        recipient,
+        &base_url,
+        &token,
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Send password reset email", skip_all)]
+async fn send_password_reset_email(
+    connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    email_templates: &EmailTemplates,
+    recipient: PasswordResetRecipient,
+    base_url: &str,
+    token: &Secret<String>,
+) -> Result<(), anyhow::Error> {
+    let recipient_email = SubscriberEmail::parse(recipient.email)
+        .map_err(|e| anyhow::anyhow!("the stored email address is invalid: {}", e))?;
+    let reset_link = format!(
+        "{}/password_reset/confirm?token={}",
+        base_url,
+        token.expose_secret()
+    );
+
+    let email = email_templates.render(&PasswordResetEmail {
+        reset_link: &reset_link,
+        expires_in_minutes: PASSWORD_RESET_TOKEN_TTL_MINUTES,
+    })?;
+
+    send_email_unless_suppressed(
+        connection_pool,
+        email_client,
+        &recipient_email,
+        "Reset your password",
+        &email.html,
+        &email.text,
+        &[],
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmFormData {
+    token: Secret<String>,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(name = "Confirm password reset", skip_all, fields(user_id = tracing::field::Empty))]
+pub async fn confirm_password_reset(
+    form: web::Form<ConfirmFormData>,
+    connection: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.0;
+    if !is_well_formed(&form.token) {
+        return Ok(invalid_token_redirect());
+    }
+    let retry_location = format!(
+        "/password_reset/confirm?token={}",
+        form.token.expose_secret()
+    );
+
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        FlashMessage::error(
+            "You entered two different new passwords - the field values must match.",
+        )
+        .send();
+        return Ok(see_other(&retry_location));
+    }
+
+    let new_password = match NewPassword::parse(form.new_password) {
+        Ok(new_password) => new_password,
+        Err(e) => {
+            FlashMessage::error(e.to_string()).send();
+            return Ok(see_other(&retry_location));
+        }
+    };
+
+    match reset_password(&form.token, new_password, &connection)
+        .await
+        .map_err(e500)?
+    {
+        Some(user_id) => {
+            tracing::Span::current().record("user_id", tracing::field::display(user_id));
+            FlashMessage::info("Your password has been reset. You can now log in.").send();
+            Ok(see_other("/login"))
+        }
+        None => Ok(invalid_token_redirect()),
+    }
+}
+
+pub(super) fn invalid_token_redirect() -> HttpResponse {
+    FlashMessage::error("This password reset link is invalid or has expired.").send();
+    see_other("/password_reset")
+}
@@ -1,12 +1,17 @@
-use actix_web::{web, HttpResponse};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
 use chrono::Utc;
-use reqwest::Error;
-use sqlx::PgPool;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::domain::SubscriberEmail;
-use crate::domain::{NewSubscriber, SubscriberName};
-use crate::email_client::EmailClient;
+use crate::domain::{IllegalTransition, NewSubscriber, SubscriberName, SubscriberNameError};
+use crate::domain::{SubscriberEmail, SubscriberEmailError, SubscriptionStatus};
+use crate::email_client::EmailSender;
+use crate::email_templates::{ConfirmationEmail, EmailTemplates, TemplateError};
+use crate::startup::ApplicationBaseUrl;
+use crate::suppression_list::{send_email_unless_suppressed, Delivery, GatedSendError};
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
@@ -16,7 +21,7 @@ pub struct FormData {
 
 #[tracing::instrument(
 name = "Adding new subscriber",
-skip(form_data, connection, email_client),
+skip(form_data, connection, email_client, email_templates, base_url),
 fields(
 subscriber_email = % form_data.email,
 subscriber_name = % form_data.name
@@ -25,69 +30,346 @@ subscriber_name = % form_data.name
 pub async fn subscribe(
     form_data: web::Form<FormData>,
     connection: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
-) -> HttpResponse {
-    let new_subscriber = match form_data.0.try_into() {
-        Ok(data) => data,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+    email_client: web::Data<dyn EmailSender>,
+    email_templates: web::Data<EmailTemplates>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, SubscribeError> {
+    let new_subscriber: NewSubscriber = form_data.0.try_into()?;
+    register_subscriber(
+        new_subscriber,
+        &connection,
+        email_client.as_ref(),
+        &email_templates,
+        &base_url.0,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Stores a new subscriber, or refreshes a pending one, and sends the confirmation email.
+/// Unsubscribed and bounced addresses are put back to pending so they can opt in again.
+/// Confirmed, complaining and suppressed subscribers are left untouched.
+pub(crate) async fn register_subscriber(
+    new_subscriber: NewSubscriber,
+    connection: &PgPool,
+    email_client: &dyn EmailSender,
+    email_templates: &EmailTemplates,
+    base_url: &str,
+) -> Result<(), SubscribeError> {
+    // The subscriber and its token are only committed once the confirmation email has been
+    // accepted, so a failed send leaves nothing behind and the user can simply retry.
+    let mut transaction = connection.begin().await?;
+
+    let subscription_token = loop {
+        let existing_subscriber =
+            get_subscriber_by_email(&mut transaction, &new_subscriber.email).await?;
+
+        break match existing_subscriber {
+            Some(subscriber) if subscriber.status == SubscriptionStatus::PendingConfirmation => {
+                update_subscriber_name(&mut transaction, subscriber.id, &new_subscriber.name)
+                    .await?;
+
+                match get_token_for_subscriber(&mut transaction, subscriber.id).await? {
+                    Some(subscription_token) => subscription_token,
+                    None => create_token(&mut transaction, subscriber.id).await?,
+                }
+            }
+            Some(subscriber)
+                if subscriber
+                    .status
+                    .can_transition_to(SubscriptionStatus::PendingConfirmation) =>
+            {
+                update_subscriber_name(&mut transaction, subscriber.id, &new_subscriber.name)
+                    .await?;
+                change_subscription_status(
+                    &mut transaction,
+                    subscriber.id,
+                    SubscriptionStatus::PendingConfirmation,
+                    "subscribed again",
+                )
+                .await?;
+                create_token(&mut transaction, subscriber.id).await?
+            }
+            Some(_) => return Ok(()),
+            None => match create_subscriber(&new_subscriber, &mut transaction).await? {
+                Some(subscriber_id) => create_token(&mut transaction, subscriber_id).await?,
+                // A concurrent request registered the same address first, so this one is
+                // handled as a repeat of it.
+                None => continue,
+            },
+        };
     };
 
-    if create_subscriber(&new_subscriber, &connection)
-        .await
-        .is_err()
-    {
-        return HttpResponse::InternalServerError().finish();
+    // Suppressed addresses are still registered, they just never get the link.
+    send_confirmation_email(
+        connection,
+        email_client,
+        email_templates,
+        new_subscriber,
+        base_url,
+        &subscription_token,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum SubscribeError {
+    #[error("invalid name: {0}")]
+    InvalidName(#[from] SubscriberNameError),
+    #[error("invalid email: {0}")]
+    InvalidEmail(#[from] SubscriberEmailError),
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("failed to send the confirmation email")]
+    SendEmailError(#[from] GatedSendError),
+    #[error("failed to render the confirmation email")]
+    TemplateError(#[from] TemplateError),
+    #[error(transparent)]
+    StatusError(#[from] SubscriptionStatusError),
+}
+
+impl std::fmt::Debug for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
     }
+}
 
-    if send_confirmation_email(&email_client, new_subscriber)
-        .await
-        .is_err()
-    {
-        return HttpResponse::InternalServerError().finish();
+impl ResponseError for SubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscribeError::InvalidName(_) | SubscribeError::InvalidEmail(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            SubscribeError::DatabaseError(_)
+            | SubscribeError::SendEmailError(_)
+            | SubscribeError::TemplateError(_)
+            | SubscribeError::StatusError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
-    HttpResponse::Ok().finish()
+    fn error_response(&self) -> HttpResponse {
+        match self.status_code() {
+            StatusCode::BAD_REQUEST => HttpResponse::BadRequest().body(self.to_string()),
+            status_code => {
+                tracing::error!("{:?}", self);
+                HttpResponse::new(status_code)
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum SubscriptionStatusError {
+    #[error(transparent)]
+    IllegalTransition(#[from] IllegalTransition),
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl std::fmt::Debug for SubscriptionStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+pub fn error_chain_fmt(
+    e: &impl std::error::Error,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    writeln!(f, "{}", e)?;
+    let mut current = e.source();
+    while let Some(cause) = current {
+        writeln!(f, "Caused by:\n\t{}", cause)?;
+        current = cause.source();
+    }
+
+    Ok(())
 }
 
 #[tracing::instrument(
     name = "sending confirmation email ",
-    skip(email_client, new_subscriber)
+    skip(
+        connection_pool,
+        email_client,
+        email_templates,
+        new_subscriber,
+        base_url,
+        subscription_token
+    )
 )]
 async fn send_confirmation_email(
-    email_client: &EmailClient,
+    connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    email_templates: &EmailTemplates,
     new_subscriber: NewSubscriber,
-) -> Result<(), Error> {
-    let confirmation_link = "https://newletter-api.com/confirmation/confirm";
-    email_client
-        .send_email(
-            new_subscriber.email,
-            "Welcome",
-            &format!(
-                "Welcome to newsletter subscription! click on <a href= \"{}\">HERE </a>",
-                confirmation_link
-            ),
-            &format!(
-                "Welcome to newsletter subscription! click on <a href= \"{}\">HERE </a>",
-                confirmation_link
-            ),
-        )
-        .await
+    base_url: &str,
+    subscription_token: &str,
+) -> Result<Delivery, SubscribeError> {
+    let confirmation_link = format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token
+    );
+    let email = email_templates.render(&ConfirmationEmail {
+        subscriber_name: new_subscriber.name.as_ref(),
+        confirmation_link: &confirmation_link,
+    })?;
+    let delivery = send_email_unless_suppressed(
+        connection_pool,
+        email_client,
+        &new_subscriber.email,
+        "Welcome",
+        &email.html,
+        &email.text,
+        &[],
+    )
+    .await?;
+
+    Ok(delivery)
 }
 
-#[tracing::instrument(name = "create subscriber", skip(new_subscriber, connection_pool))]
+#[tracing::instrument(name = "create subscriber", skip(new_subscriber, transaction))]
 async fn create_subscriber(
     new_subscriber: &NewSubscriber,
-    connection_pool: &PgPool,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let status = SubscriptionStatus::PendingConfirmation;
+    let inserted = sqlx::query!(
         r#"INSERT INTO subscriptions(id, email, name, subscribed_at, status)
-        VALUES($1, $2, $3, $4,'pending-confirmation')"#,
+        VALUES($1, $2, $3, $4, $5)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id"#,
         Uuid::new_v4(),
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
+        Utc::now(),
+        status.as_str()
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to execute query: {:?}", e);
+        e
+    })?;
+
+    let Some(inserted) = inserted else {
+        return Ok(None);
+    };
+    record_status_transition(transaction, inserted.id, None, status, "subscribed").await?;
+
+    Ok(Some(inserted.id))
+}
+
+/// Moves a subscriber to `next` and records the transition with `reason`.
+/// Returns `false` without recording anything if the subscriber is already in that state.
+#[tracing::instrument(name = "change subscription status", skip(transaction))]
+pub(crate) async fn change_subscription_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    next: SubscriptionStatus,
+    reason: &str,
+) -> Result<bool, SubscriptionStatusError> {
+    let row = sqlx::query!(
+        r#"SELECT status FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    let current = parse_status(&row.status)?;
+    if current == next {
+        return Ok(false);
+    }
+    current.transition_to(next)?;
+
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = $1 WHERE id = $2"#,
+        next.as_str(),
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    record_status_transition(transaction, subscriber_id, Some(current), next, reason).await?;
+
+    Ok(true)
+}
+
+async fn record_status_transition(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    from: Option<SubscriptionStatus>,
+    to: SubscriptionStatus,
+    reason: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO subscription_status_transitions(
+            subscriber_id, from_status, to_status, reason, transitioned_at
+        )
+        VALUES($1, $2, $3, $4, $5)"#,
+        subscriber_id,
+        from.map(|status| status.as_str()),
+        to.as_str(),
+        reason,
         Utc::now()
     )
-    .execute(connection_pool)
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+fn parse_status(status: &str) -> Result<SubscriptionStatus, sqlx::Error> {
+    SubscriptionStatus::parse(status).ok_or_else(|| {
+        sqlx::Error::Decode(format!("unknown subscription status `{status}`").into())
+    })
+}
+
+struct ExistingSubscriber {
+    id: Uuid,
+    status: SubscriptionStatus,
+}
+
+#[tracing::instrument(name = "get subscriber by email", skip(transaction, email))]
+async fn get_subscriber_by_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<ExistingSubscriber>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT id, status FROM subscriptions WHERE email = $1 FOR UPDATE"#,
+        email.as_ref()
+    )
+    .fetch_optional(transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to execute query: {:?}", e);
+        e
+    })?;
+
+    result
+        .map(|r| {
+            Ok(ExistingSubscriber {
+                id: r.id,
+                status: parse_status(&r.status)?,
+            })
+        })
+        .transpose()
+}
+
+#[tracing::instrument(name = "update subscriber name", skip(transaction, name))]
+async fn update_subscriber_name(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    name: &SubscriberName,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET name = $1 WHERE id = $2"#,
+        name.as_ref(),
+        subscriber_id
+    )
+    .execute(transaction)
     .await
     .map_err(|e| {
         tracing::error!("failed to execute query: {:?}", e);
@@ -97,8 +379,69 @@ async fn create_subscriber(
     Ok(())
 }
 
+#[tracing::instrument(name = "get token for subscriber", skip(transaction))]
+async fn get_token_for_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT subscription_token FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(result.map(|r| r.subscription_token))
+}
+
+async fn create_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<String, sqlx::Error> {
+    let subscription_token = generate_subscription_token();
+    store_token(transaction, subscriber_id, &subscription_token).await?;
+    Ok(subscription_token)
+}
+
+#[tracing::instrument(
+    name = "store subscription token",
+    skip(transaction, subscription_token)
+)]
+async fn store_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    subscription_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO subscription_tokens(subscription_token, subscriber_id)
+        VALUES($1, $2)"#,
+        subscription_token,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(())
+}
+
+fn generate_subscription_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
+}
+
 impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+    type Error = SubscribeError;
 
     fn try_from(value: FormData) -> Result<Self, Self::Error> {
         let name = SubscriberName::parse(value.name)?;
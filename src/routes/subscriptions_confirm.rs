@@ -0,0 +1,79 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::SubscriptionStatus;
+use crate::routes::subscriptions::{change_subscription_status, SubscriptionStatusError};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    subscription_token: String,
+}
+
+#[tracing::instrument(name = "Confirming pending subscriber", skip(parameters, connection))]
+pub async fn confirm(
+    parameters: web::Query<Parameters>,
+    connection: web::Data<PgPool>,
+) -> HttpResponse {
+    let subscriber_id =
+        match get_subscriber_id_from_token(&connection, &parameters.subscription_token).await {
+            Ok(subscriber_id) => subscriber_id,
+            Err(_) => return HttpResponse::InternalServerError().finish(),
+        };
+
+    match subscriber_id {
+        None => HttpResponse::Unauthorized().finish(),
+        Some(subscriber_id) => match confirm_subscriber(&connection, subscriber_id).await {
+            Ok(()) => HttpResponse::Ok().finish(),
+            // Bounced, complaining or suppressed addresses cannot be confirmed with an old link.
+            Err(SubscriptionStatusError::IllegalTransition(_)) => {
+                HttpResponse::Unauthorized().finish()
+            }
+            Err(_) => HttpResponse::InternalServerError().finish(),
+        },
+    }
+}
+
+#[tracing::instrument(name = "confirm subscriber", skip(connection_pool))]
+async fn confirm_subscriber(
+    connection_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<(), SubscriptionStatusError> {
+    let mut transaction = connection_pool.begin().await?;
+    change_subscription_status(
+        &mut transaction,
+        subscriber_id,
+        SubscriptionStatus::Confirmed,
+        "confirmation link",
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to confirm subscriber: {:?}", e);
+        e
+    })?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "get subscriber id from token",
+    skip(connection_pool, subscription_token)
+)]
+async fn get_subscriber_id_from_token(
+    connection_pool: &PgPool,
+    subscription_token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1"#,
+        subscription_token
+    )
+    .fetch_optional(connection_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(result.map(|r| r.subscriber_id))
+}
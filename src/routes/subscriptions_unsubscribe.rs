@@ -0,0 +1,143 @@
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{SubscriptionStatus, UnsubscribeToken};
+use crate::routes::error_chain_fmt;
+use crate::routes::subscriptions::{change_subscription_status, SubscriptionStatusError};
+use crate::startup::HmacSecret;
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Unsubscribe confirmation page", skip(parameters, hmac_secret))]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    UnsubscribeToken::verify(&parameters.token, &hmac_secret.0)
+        .ok_or(UnsubscribeError::InvalidToken)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribe</title>
+</head>
+<body>
+    <p>Do you want to stop receiving our newsletter?</p>
+    <form action="/subscriptions/unsubscribe?token={}" method="post">
+        <button type="submit">Unsubscribe</button>
+    </form>
+</body>
+</html>"#,
+            parameters.token
+        )))
+}
+
+/// Handles both the confirmation page and one-click requests from mail clients
+/// (RFC 8058), which POST `List-Unsubscribe=One-Click` to the link from the header.
+#[tracing::instrument(
+    name = "Unsubscribing subscriber",
+    skip(parameters, connection, hmac_secret),
+    fields(subscriber_id = tracing::field::Empty)
+)]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    connection: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let subscriber_id = UnsubscribeToken::verify(&parameters.token, &hmac_secret.0)
+        .ok_or(UnsubscribeError::InvalidToken)?;
+    tracing::Span::current().record("subscriber_id", tracing::field::display(subscriber_id));
+
+    unsubscribe_subscriber(&connection, subscriber_id).await?;
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribed</title>
+</head>
+<body>
+    <p>You have been unsubscribed. You will not receive any more issues.</p>
+</body>
+</html>"#,
+    ))
+}
+
+/// Complaining and suppressed subscribers already receive nothing, so for them this is a no-op.
+#[tracing::instrument(name = "unsubscribe subscriber", skip(connection_pool))]
+async fn unsubscribe_subscriber(
+    connection_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<(), UnsubscribeError> {
+    let mut transaction = connection_pool.begin().await?;
+    match change_subscription_status(
+        &mut transaction,
+        subscriber_id,
+        SubscriptionStatus::Unsubscribed,
+        "unsubscribe link",
+    )
+    .await
+    {
+        Ok(_) | Err(SubscriptionStatusError::IllegalTransition(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    // Old confirmation links must not bring the subscriber back without a new sign-up.
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("the unsubscribe link is invalid")]
+    InvalidToken,
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error(transparent)]
+    StatusError(#[from] SubscriptionStatusError),
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UnsubscribeError::InvalidToken => StatusCode::UNAUTHORIZED,
+            UnsubscribeError::DatabaseError(_) | UnsubscribeError::StatusError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self.status_code() {
+            StatusCode::UNAUTHORIZED => HttpResponse::Unauthorized().body(self.to_string()),
+            status_code => {
+                tracing::error!("{:?}", self);
+                HttpResponse::new(status_code)
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::TrackingSettings;
+use crate::domain::{ClickToken, OpenToken, TrackedLink};
+use crate::routes::error_chain_fmt;
+use crate::startup::HmacSecret;
+
+// A transparent 1x1 GIF.
+const TRACKING_IMAGE: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+/// Records a click on a tracked link, then redirects to its target. The reader is redirected
+/// even if the click could not be recorded: a lost click matters less than a broken link.
+#[tracing::instrument(
+    name = "Track click",
+    skip(token, connection, hmac_secret, tracking_settings),
+    fields(subscriber_id = tracing::field::Empty, newsletter_issue_id = tracing::field::Empty)
+)]
+pub async fn track_click(
+    token: web::Path<String>,
+    connection: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    tracking_settings: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, TrackingError> {
+    let link = ClickToken::verify(&token, &hmac_secret.0).ok_or(TrackingError::InvalidToken)?;
+    let span = tracing::Span::current();
+    span.record("subscriber_id", tracing::field::display(link.subscriber_id));
+    span.record(
+        "newsletter_issue_id",
+        tracing::field::display(link.newsletter_issue_id),
+    );
+
+    if tracking_settings.clicks {
+        if let Err(e) = record_click(&connection, &link).await {
+            tracing::error!("failed to record a click: {:?}", e);
+        }
+    }
+
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, link.url))
+        .finish())
+}
+
+#[tracing::instrument(name = "record click", skip(connection_pool))]
+async fn record_click(connection_pool: &PgPool, link: &TrackedLink) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO link_clicks (newsletter_issue_id, subscriber_id, url, clicked_at)
+        VALUES ($1, $2, $3, $4)"#,
+        link.newsletter_issue_id,
+        link.subscriber_id,
+        link.url,
+        Utc::now()
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Serves the tracking image of a delivery, recording the open. Like clicks, opens that could
+/// not be recorded are only logged.
+#[tracing::instrument(
+    name = "Track open",
+    skip(token, connection, hmac_secret, tracking_settings),
+    fields(subscriber_id = tracing::field::Empty, newsletter_issue_id = tracing::field::Empty)
+)]
+pub async fn track_open(
+    token: web::Path<String>,
+    connection: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    tracking_settings: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, TrackingError> {
+    let (subscriber_id, newsletter_issue_id) =
+        OpenToken::verify(&token, &hmac_secret.0).ok_or(TrackingError::InvalidToken)?;
+    let span = tracing::Span::current();
+    span.record("subscriber_id", tracing::field::display(subscriber_id));
+    span.record(
+        "newsletter_issue_id",
+        tracing::field::display(newsletter_issue_id),
+    );
+
+    if tracking_settings.opens {
+        if let Err(e) = record_open(&connection, subscriber_id, newsletter_issue_id).await {
+            tracing::error!("failed to record an open: {:?}", e);
+        }
+    }
+
+    // Every open has to reach us, not a cached copy of the image.
+    Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![
+            CacheDirective::NoCache,
+            CacheDirective::NoStore,
+            CacheDirective::MustRevalidate,
+        ]))
+        .body(TRACKING_IMAGE.as_slice()))
+}
+
+#[tracing::instrument(name = "record open", skip(connection_pool))]
+async fn record_open(
+    connection_pool: &PgPool,
+    subscriber_id: Uuid,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO email_opens
+            (newsletter_issue_id, subscriber_id, n_opens, first_opened_at, last_opened_at)
+        VALUES ($1, $2, 1, $3, $3)
+        ON CONFLICT (newsletter_issue_id, subscriber_id) DO UPDATE
+        SET n_opens = email_opens.n_opens + 1, last_opened_at = EXCLUDED.last_opened_at"#,
+        newsletter_issue_id,
+        subscriber_id,
+        Utc::now()
+    )
+    .execute(connection_pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum TrackingError {
+    #[error("the link is invalid")]
+    InvalidToken,
+}
+
+impl std::fmt::Debug for TrackingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for TrackingError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TrackingError::InvalidToken => StatusCode::NOT_FOUND,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::NotFound().body(self.to_string())
+    }
+}
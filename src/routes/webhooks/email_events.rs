@@ -0,0 +1,175 @@
+use actix_web::http::header::{HeaderValue, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::authentication::{basic_authentication, validate_configured_credentials, AuthError};
+use crate::configuration::EmailWebhookSettings;
+use crate::domain::SubscriptionStatus;
+use crate::routes::error_chain_fmt;
+use crate::routes::subscriptions::SubscriptionStatusError;
+use crate::suppression_list::{suppress_subscriber, SuppressionSource};
+
+// Postmark bounce types that mean the address will never accept mail. Soft bounces,
+// auto-responders and the like are recorded but do not affect the subscriber.
+const PERMANENT_BOUNCE_TYPES: [&str; 3] = ["HardBounce", "BadEmailAddress", "Inactive"];
+
+/// The parts of a Postmark webhook payload we act on. The full payload is stored as well.
+#[derive(serde::Deserialize)]
+#[serde(tag = "RecordType")]
+enum EmailEvent {
+    #[serde(rename_all = "PascalCase")]
+    Bounce {
+        #[serde(rename = "Type")]
+        bounce_type: String,
+        email: String,
+        description: Option<String>,
+    },
+    #[serde(rename_all = "PascalCase")]
+    SpamComplaint { email: String },
+    #[serde(rename_all = "PascalCase")]
+    Delivery { recipient: String },
+    #[serde(other)]
+    Other,
+}
+
+#[tracing::instrument(
+    name = "Receive email event",
+    skip_all,
+    fields(event_type = tracing::field::Empty, email = tracing::field::Empty)
+)]
+pub async fn receive_email_event(
+    request: HttpRequest,
+    payload: web::Json<serde_json::Value>,
+    connection: web::Data<PgPool>,
+    webhook_settings: web::Data<EmailWebhookSettings>,
+) -> Result<HttpResponse, EmailEventError> {
+    let credentials = basic_authentication(request.headers())?;
+    validate_configured_credentials(
+        &credentials,
+        &webhook_settings.username,
+        &webhook_settings.password,
+    )?;
+
+    let payload = payload.into_inner();
+    let event: EmailEvent =
+        serde_json::from_value(payload.clone()).map_err(EmailEventError::InvalidPayload)?;
+    let (event_type, email) = match &event {
+        EmailEvent::Bounce { email, .. } => ("bounce", email),
+        EmailEvent::SpamComplaint { email } => ("spam_complaint", email),
+        EmailEvent::Delivery { recipient } => ("delivery", recipient),
+        // Acknowledge events we have no use for, otherwise the provider keeps retrying them.
+        EmailEvent::Other => return Ok(HttpResponse::Ok().finish()),
+    };
+    tracing::Span::current()
+        .record("event_type", event_type)
+        .record("email", tracing::field::display(email));
+
+    let mut transaction = connection.begin().await?;
+    record_email_event(&mut transaction, event_type, email, &payload).await?;
+    match &event {
+        EmailEvent::Bounce {
+            bounce_type,
+            email,
+            description,
+        } if PERMANENT_BOUNCE_TYPES.contains(&bounce_type.as_str()) => {
+            let reason = match description {
+                Some(description) => format!("{}: {}", bounce_type, description),
+                None => bounce_type.clone(),
+            };
+            suppress_subscriber(
+                &mut transaction,
+                email,
+                SubscriptionStatus::Bounced,
+                &reason,
+                SuppressionSource::Bounce,
+            )
+            .await?;
+        }
+        EmailEvent::SpamComplaint { email } => {
+            suppress_subscriber(
+                &mut transaction,
+                email,
+                SubscriptionStatus::Complained,
+                "spam complaint",
+                SuppressionSource::Complaint,
+            )
+            .await?;
+        }
+        EmailEvent::Bounce { .. } | EmailEvent::Delivery { .. } | EmailEvent::Other => {}
+    }
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(name = "record email event", skip(transaction, payload))]
+async fn record_email_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event_type: &str,
+    email: &str,
+    payload: &serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO email_events(event_type, email, payload, received_at)
+        VALUES($1, $2, $3, $4)"#,
+        event_type,
+        email,
+        payload,
+        Utc::now()
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailEventError {
+    #[error("authentication failed")]
+    AuthError(#[from] AuthError),
+    #[error("invalid event payload: {0}")]
+    InvalidPayload(serde_json::Error),
+    #[error("failed to execute a database query")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error(transparent)]
+    StatusError(#[from] SubscriptionStatusError),
+}
+
+impl std::fmt::Debug for EmailEventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for EmailEventError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            EmailEventError::AuthError(AuthError::MissingCredentials(_))
+            | EmailEventError::AuthError(AuthError::InvalidCredentials) => StatusCode::UNAUTHORIZED,
+            EmailEventError::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+            EmailEventError::AuthError(_)
+            | EmailEventError::DatabaseError(_)
+            | EmailEventError::StatusError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self.status_code() {
+            StatusCode::UNAUTHORIZED => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                response.headers_mut().insert(
+                    WWW_AUTHENTICATE,
+                    HeaderValue::from_static(r#"Basic realm="email-events""#),
+                );
+                response
+            }
+            StatusCode::BAD_REQUEST => HttpResponse::BadRequest().body(self.to_string()),
+            status_code => {
+                tracing::error!("{:?}", self);
+                HttpResponse::new(status_code)
+            }
+        }
+    }
+}
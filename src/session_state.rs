@@ -0,0 +1,65 @@
+use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+use uuid::Uuid;
+
+pub struct TypedSession(Session);
+
+impl TypedSession {
+    const USER_ID_KEY: &'static str = "user_id";
+    const PENDING_USER_ID_KEY: &'static str = "pending_two_factor_user_id";
+    const TWO_FACTOR_FAILURES_KEY: &'static str = "two_factor_failures";
+
+    pub fn renew(&self) {
+        self.0.renew();
+    }
+
+    pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::USER_ID_KEY, user_id)
+    }
+
+    pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::USER_ID_KEY)
+    }
+
+    /// Marks a user whose password checked out but who still owes a second factor.
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::TWO_FACTOR_FAILURES_KEY);
+    }
+
+    /// Records a failed second factor attempt and returns how many there have been.
+    pub fn record_two_factor_failure(&self) -> Result<u32, SessionInsertError> {
+        let failures = self
+            .0
+            .get::<u32>(Self::TWO_FACTOR_FAILURES_KEY)
+            .ok()
+            .flatten()
+            .unwrap_or(0)
+            + 1;
+        self.0.insert(Self::TWO_FACTOR_FAILURES_KEY, failures)?;
+        Ok(failures)
+    }
+
+    pub fn log_out(self) {
+        self.0.purge()
+    }
+}
+
+impl FromRequest for TypedSession {
+    type Error = <Session as FromRequest>::Error;
+    type Future = Ready<Result<TypedSession, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(TypedSession(req.get_session())))
+    }
+}
@@ -0,0 +1,103 @@
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use super::{generate_session_key, SessionState};
+
+#[derive(Clone, Default)]
+pub struct InMemorySessionStore {
+    sessions: Arc<Mutex<HashMap<String, (SessionState, Instant)>>>,
+}
+
+fn expires_at(ttl: &Duration) -> Instant {
+    Instant::now() + std::time::Duration::from_secs(ttl.whole_seconds().max(0) as u64)
+}
+
+impl SessionStore for InMemorySessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let sessions = self.sessions.lock().unwrap();
+
+        Ok(sessions
+            .get(session_key.as_ref())
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(session_state, _)| session_state.clone()))
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let session_key = generate_session_key();
+        self.sessions.lock().unwrap().insert(
+            session_key.as_ref().to_owned(),
+            (session_state, expires_at(ttl)),
+        );
+
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        self.sessions.lock().unwrap().insert(
+            session_key.as_ref().to_owned(),
+            (session_state, expires_at(ttl)),
+        );
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        if let Some((_, expires)) = self.sessions.lock().unwrap().get_mut(session_key.as_ref()) {
+            *expires = expires_at(ttl);
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        self.sessions.lock().unwrap().remove(session_key.as_ref());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InMemorySessionStore;
+    use actix_session::storage::SessionStore;
+    use actix_web::cookie::time::Duration;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn a_saved_session_can_be_loaded_until_it_is_deleted() {
+        let store = InMemorySessionStore::default();
+        let session_state = HashMap::from([("user_id".to_string(), "\"42\"".to_string())]);
+
+        let session_key = store
+            .save(session_state.clone(), &Duration::minutes(5))
+            .await
+            .unwrap();
+        assert_eq!(store.load(&session_key).await.unwrap(), Some(session_state));
+
+        store.delete(&session_key).await.unwrap();
+        assert_eq!(store.load(&session_key).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn an_expired_session_is_not_loaded() {
+        let store = InMemorySessionStore::default();
+
+        let session_key = store
+            .save(HashMap::new(), &Duration::seconds(0))
+            .await
+            .unwrap();
+        assert_eq!(store.load(&session_key).await.unwrap(), None);
+    }
+}
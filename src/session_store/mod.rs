@@ -0,0 +1,79 @@
+mod in_memory;
+mod postgres;
+
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+
+pub use in_memory::InMemorySessionStore;
+pub use postgres::PostgresSessionStore;
+
+type SessionState = HashMap<String, String>;
+
+// `SessionMiddleware` is generic over its store, so the backend picked in the configuration is
+// wrapped in a single type that delegates to it.
+#[derive(Clone)]
+pub enum SessionBackend {
+    Postgres(PostgresSessionStore),
+    InMemory(InMemorySessionStore),
+}
+
+impl SessionStore for SessionBackend {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        match self {
+            SessionBackend::Postgres(store) => store.load(session_key).await,
+            SessionBackend::InMemory(store) => store.load(session_key).await,
+        }
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        match self {
+            SessionBackend::Postgres(store) => store.save(session_state, ttl).await,
+            SessionBackend::InMemory(store) => store.save(session_state, ttl).await,
+        }
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        match self {
+            SessionBackend::Postgres(store) => store.update(session_key, session_state, ttl).await,
+            SessionBackend::InMemory(store) => store.update(session_key, session_state, ttl).await,
+        }
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        match self {
+            SessionBackend::Postgres(store) => store.update_ttl(session_key, ttl).await,
+            SessionBackend::InMemory(store) => store.update_ttl(session_key, ttl).await,
+        }
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        match self {
+            SessionBackend::Postgres(store) => store.delete(session_key).await,
+            SessionBackend::InMemory(store) => store.delete(session_key).await,
+        }
+    }
+}
+
+fn generate_session_key() -> SessionKey {
+    let mut rng = thread_rng();
+    let session_key: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(64)
+        .collect();
+
+    session_key
+        .try_into()
+        .expect("a 64 characters alphanumeric string is a valid session key")
+}
@@ -0,0 +1,117 @@
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use super::{generate_session_key, SessionState};
+
+#[derive(Clone)]
+pub struct PostgresSessionStore {
+    connection_pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(connection_pool: PgPool) -> Self {
+        Self { connection_pool }
+    }
+}
+
+fn expires_at(ttl: &Duration) -> chrono::DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())
+}
+
+impl SessionStore for PostgresSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let row = sqlx::query!(
+            r#"SELECT session_state FROM sessions
+            WHERE session_key = $1 AND expires_at > now()"#,
+            session_key.as_ref()
+        )
+        .fetch_optional(&self.connection_pool)
+        .await
+        .map_err(|e| LoadError::Other(e.into()))?;
+
+        row.map(|r| serde_json::from_value(r.session_state))
+            .transpose()
+            .map_err(|e| LoadError::Deserialization(e.into()))
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let session_state =
+            serde_json::to_value(session_state).map_err(|e| SaveError::Serialization(e.into()))?;
+        let session_key = generate_session_key();
+
+        sqlx::query!(
+            r#"INSERT INTO sessions(session_key, session_state, expires_at)
+            VALUES($1, $2, $3)"#,
+            session_key.as_ref(),
+            session_state,
+            expires_at(ttl)
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map_err(|e| SaveError::Other(e.into()))?;
+
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let session_state = serde_json::to_value(session_state)
+            .map_err(|e| UpdateError::Serialization(e.into()))?;
+
+        let n_updated_rows = sqlx::query!(
+            r#"UPDATE sessions SET session_state = $2, expires_at = $3
+            WHERE session_key = $1 AND expires_at > now()"#,
+            session_key.as_ref(),
+            session_state,
+            expires_at(ttl)
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map_err(|e| UpdateError::Other(e.into()))?
+        .rows_affected();
+
+        if n_updated_rows == 0 {
+            let session_state = serde_json::from_value(session_state)
+                .map_err(|e| UpdateError::Serialization(e.into()))?;
+            return self.save(session_state, ttl).await.map_err(|e| match e {
+                SaveError::Serialization(e) => UpdateError::Serialization(e),
+                SaveError::Other(e) => UpdateError::Other(e),
+            });
+        }
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"UPDATE sessions SET expires_at = $2 WHERE session_key = $1"#,
+            session_key.as_ref(),
+            expires_at(ttl)
+        )
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE session_key = $1"#,
+            session_key.as_ref()
+        )
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(())
+    }
+}
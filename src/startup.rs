@@ -1,31 +1,200 @@
-use crate::configuration::Settings;
-use crate::email_client::EmailClient;
+use crate::authentication::reject_anonymous_users;
+use crate::clock::{Clock, SystemClock};
+use crate::configuration::{EmailWebhookSettings, SessionStoreKind, Settings, TrackingSettings};
+use crate::email_client::EmailSender;
+use crate::email_templates::EmailTemplates;
+use crate::issue_delivery_worker::run_worker_until_stopped;
+use crate::session_store::{InMemorySessionStore, PostgresSessionStore, SessionBackend};
+use actix_session::SessionMiddleware;
+use actix_web::cookie::Key;
+use actix_web::middleware::from_fn;
 use actix_web::{dev::Server, web, App, HttpServer};
+use actix_web_flash_messages::storage::CookieMessageStore;
+use actix_web_flash_messages::FlashMessagesFramework;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::net::TcpListener;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
 use tracing_actix_web::TracingLogger;
 
 pub struct Application {
     port: u16,
     server: Server,
+    worker: JoinHandle<()>,
 }
 
+pub struct ApplicationBaseUrl(pub String);
+
+#[derive(Clone)]
+pub struct HmacSecret(pub Secret<String>);
+
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     listener: TcpListener,
     connection: PgPool,
-    email_client: EmailClient,
+    email_client: Arc<dyn EmailSender>,
+    email_templates: Arc<EmailTemplates>,
+    base_url: String,
+    hmac_secret: Secret<String>,
+    session_store: SessionBackend,
+    email_webhook_settings: EmailWebhookSettings,
+    tracking_settings: TrackingSettings,
+    clock: Arc<dyn Clock>,
 ) -> Result<Server, Error> {
+    let secret_key = Key::try_from(hmac_secret.expose_secret().as_bytes())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let message_store = CookieMessageStore::builder(secret_key.clone()).build();
+    let message_framework = FlashMessagesFramework::builder(message_store).build();
     let connection = web::Data::new(connection);
-    let email_client = web::Data::new(email_client);
+    let email_client: web::Data<dyn EmailSender> = web::Data::from(email_client);
+    let email_templates = web::Data::from(email_templates);
+    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let hmac_secret = web::Data::new(HmacSecret(hmac_secret));
+    let email_webhook_settings = web::Data::new(email_webhook_settings);
+    let tracking_settings = web::Data::new(tracking_settings);
+    let clock: web::Data<dyn Clock> = web::Data::from(clock);
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(message_framework.clone())
+            .wrap(SessionMiddleware::new(
+                session_store.clone(),
+                secret_key.clone(),
+            ))
             .wrap(TracingLogger::default())
             .route("/health_check", web::get().to(crate::routes::health_check))
             .route("/subscriptions", web::post().to(crate::routes::subscribe))
+            .route(
+                "/subscriptions/confirm",
+                web::get().to(crate::routes::confirm),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(crate::routes::unsubscribe_form),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::post().to(crate::routes::unsubscribe),
+            )
+            .route(
+                "/newsletters",
+                web::post().to(crate::routes::publish_newsletter),
+            )
+            .route("/t/c/{token}", web::get().to(crate::routes::track_click))
+            .route("/t/o/{token}.gif", web::get().to(crate::routes::track_open))
+            .route(
+                "/api/subscribers",
+                web::get().to(crate::routes::list_subscribers),
+            )
+            .route(
+                "/api/subscribers",
+                web::post().to(crate::routes::add_subscriber),
+            )
+            .route(
+                "/webhooks/email-events",
+                web::post().to(crate::routes::receive_email_event),
+            )
+            .route("/login", web::get().to(crate::routes::login_form))
+            .route("/login", web::post().to(crate::routes::login))
+            .route(
+                "/login/two_factor",
+                web::get().to(crate::routes::two_factor_form),
+            )
+            .route(
+                "/login/two_factor",
+                web::post().to(crate::routes::verify_two_factor),
+            )
+            .route(
+                "/password_reset",
+                web::get().to(crate::routes::password_reset_form),
+            )
+            .route(
+                "/password_reset",
+                web::post().to(crate::routes::request_password_reset),
+            )
+            .route(
+                "/password_reset/confirm",
+                web::get().to(crate::routes::password_reset_confirm_form),
+            )
+            .route(
+                "/password_reset/confirm",
+                web::post().to(crate::routes::confirm_password_reset),
+            )
+            .service(
+                web::scope("/admin")
+                    .wrap(from_fn(reject_anonymous_users))
+                    .route("/dashboard", web::get().to(crate::routes::admin_dashboard))
+                    .route("/logout", web::post().to(crate::routes::log_out))
+                    .route(
+                        "/password",
+                        web::get().to(crate::routes::change_password_form),
+                    )
+                    .route("/password", web::post().to(crate::routes::change_password))
+                    .route(
+                        "/two_factor",
+                        web::get().to(crate::routes::two_factor_settings),
+                    )
+                    .route(
+                        "/two_factor/enroll",
+                        web::post().to(crate::routes::enroll_two_factor),
+                    )
+                    .route(
+                        "/two_factor/confirm",
+                        web::post().to(crate::routes::confirm_two_factor),
+                    )
+                    .route(
+                        "/two_factor/disable",
+                        web::post().to(crate::routes::disable_two_factor),
+                    )
+                    .route(
+                        "/two_factor/policy",
+                        web::post().to(crate::routes::update_two_factor_policy),
+                    )
+                    .route("/api_keys", web::get().to(crate::routes::get_api_keys))
+                    .route("/api_keys", web::post().to(crate::routes::issue_api_key))
+                    .route(
+                        "/api_keys/{api_key_id}/revoke",
+                        web::post().to(crate::routes::revoke_key),
+                    )
+                    .route(
+                        "/suppressions",
+                        web::get().to(crate::routes::get_suppressions),
+                    )
+                    .route(
+                        "/suppressions",
+                        web::post().to(crate::routes::suppress_address),
+                    )
+                    .route(
+                        "/suppressions/remove",
+                        web::post().to(crate::routes::unsuppress_address),
+                    )
+                    .route(
+                        "/dead_letters",
+                        web::get().to(crate::routes::list_dead_letters),
+                    )
+                    .route(
+                        "/dead_letters/requeue",
+                        web::post().to(crate::routes::requeue_dead_letters),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/clicks",
+                        web::get().to(crate::routes::list_issue_clicks),
+                    )
+                    .route(
+                        "/newsletters/{newsletter_issue_id}/stats",
+                        web::get().to(crate::routes::get_issue_stats),
+                    ),
+            )
             .app_data(connection.clone())
             .app_data(email_client.clone())
+            .app_data(email_templates.clone())
+            .app_data(base_url.clone())
+            .app_data(hmac_secret.clone())
+            .app_data(email_webhook_settings.clone())
+            .app_data(tracking_settings.clone())
+            .app_data(clock.clone())
     })
     .listen(listener)?
     .run();
@@ -41,6 +210,21 @@ pub fn get_connection_pool(settings: &Settings) -> PgPool {
 
 impl Application {
     pub async fn build(settings: Settings) -> Result<Self, Error> {
+        let email_client = settings
+            .email_client_settings
+            .client()
+            .map_err(Error::other)?;
+        Self::build_with(settings, email_client, Arc::new(SystemClock)).await
+    }
+
+    /// Like [`Application::build`], sending every email through `email_client` whatever the
+    /// settings say and reading the time from `clock`. Lets tests record outgoing email instead
+    /// of sending it, and pin the time.
+    pub async fn build_with(
+        settings: Settings,
+        email_client: Arc<dyn EmailSender>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self, Error> {
         let address = format!(
             "{}:{}",
             settings.application_host_address, settings.application_port
@@ -48,20 +232,51 @@ impl Application {
 
         let listener = TcpListener::bind(address)?;
         let connection_pool = get_connection_pool(&settings);
-        let sender_email = settings
-            .email_client_settings
-            .sender_email()
-            .expect("Invalid subscription email sender address");
-        let email_client = EmailClient::new(
-            settings.email_client_settings.base_url,
-            sender_email,
-            settings.email_client_settings.auth_token,
+        let email_templates = Arc::new(
+            EmailTemplates::load(
+                &settings.email_template_settings,
+                &settings.application_base_url,
+                &connection_pool,
+            )
+            .await
+            .map_err(Error::other)?,
         );
+        let worker = tokio::spawn(run_worker_until_stopped(
+            connection_pool.clone(),
+            email_client.clone(),
+            email_templates.clone(),
+            settings.issue_delivery_settings.clone(),
+            settings.tracking_settings,
+            settings.application_base_url.clone(),
+            settings.hmac_secret.clone(),
+        ));
+
+        let session_store = match settings.session_store {
+            SessionStoreKind::Postgres => {
+                SessionBackend::Postgres(PostgresSessionStore::new(connection_pool.clone()))
+            }
+            SessionStoreKind::InMemory => SessionBackend::InMemory(InMemorySessionStore::default()),
+        };
 
         let port = listener.local_addr().unwrap().port();
-        let server = run(listener, connection_pool, email_client)?;
+        let server = run(
+            listener,
+            connection_pool,
+            email_client,
+            email_templates,
+            settings.application_base_url,
+            settings.hmac_secret,
+            session_store,
+            settings.email_webhook_settings,
+            settings.tracking_settings,
+            clock,
+        )?;
 
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            worker,
+        })
     }
 
     pub fn port(&self) -> u16 {
@@ -69,6 +284,12 @@ impl Application {
     }
 
     pub async fn run_until_stoped(self) -> Result<(), Error> {
-        self.server.await
+        tokio::select! {
+            outcome = self.server => outcome,
+            outcome = self.worker => {
+                tracing::error!("Issue delivery worker stopped: {:?}", outcome);
+                Err(Error::other("issue delivery worker stopped"))
+            }
+        }
     }
 }
@@ -0,0 +1,293 @@
+//! Addresses that must not receive any more email, whatever their subscription status says.
+//! Addresses are compared case-insensitively, since providers do not echo back our casing.
+//!
+//! Every outgoing email goes through [`send_email_unless_suppressed`] or
+//! [`send_batch_unless_suppressed`], so a new send path cannot forget to check the list.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashSet;
+
+use crate::domain::{SubscriberEmail, SubscriptionStatus};
+use crate::email_client::{EmailHeader, EmailSender, OutgoingEmail, SendEmailError};
+use crate::routes::error_chain_fmt;
+use crate::routes::{change_subscription_status, SubscriptionStatusError};
+
+/// Why an address ended up on the list.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionSource {
+    /// Added by an admin.
+    Manual,
+    /// A hard bounce reported by the email provider.
+    Bounce,
+    /// A spam complaint reported by the email provider.
+    Complaint,
+    /// An erasure or do-not-contact request we are legally bound by.
+    Legal,
+}
+
+impl SuppressionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionSource::Manual => "manual",
+            SuppressionSource::Bounce => "bounce",
+            SuppressionSource::Complaint => "complaint",
+            SuppressionSource::Legal => "legal",
+        }
+    }
+
+    pub fn parse(source: &str) -> Option<Self> {
+        [
+            SuppressionSource::Manual,
+            SuppressionSource::Bounce,
+            SuppressionSource::Complaint,
+            SuppressionSource::Legal,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == source)
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct Suppression {
+    pub email: String,
+    pub reason: String,
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub enum Delivery {
+    Sent,
+    Suppressed,
+}
+
+#[derive(thiserror::Error)]
+pub enum GatedSendError {
+    #[error("failed to check the suppression list")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("failed to send the email")]
+    SendEmailError(#[from] SendEmailError),
+}
+
+impl std::fmt::Debug for GatedSendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Sends the email unless the recipient is on the suppression list.
+#[tracing::instrument(
+    name = "Send email unless suppressed",
+    skip_all,
+    fields(recipient = %recipient.as_ref())
+)]
+pub async fn send_email_unless_suppressed(
+    connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    recipient: &SubscriberEmail,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    headers: &[EmailHeader<'_>],
+) -> Result<Delivery, GatedSendError> {
+    if is_suppressed(connection_pool, recipient.as_ref()).await? {
+        tracing::info!("The recipient is on the suppression list. Not sending");
+        return Ok(Delivery::Suppressed);
+    }
+
+    email_client
+        .send_email_with_headers(recipient, subject, html_content, text_content, headers)
+        .await?;
+
+    Ok(Delivery::Sent)
+}
+
+/// Sends the emails whose recipients are not on the suppression list as one batch. Returns
+/// one result per email, in order; only checking the list can fail the batch as a whole.
+#[tracing::instrument(
+    name = "Send batch unless suppressed",
+    skip_all,
+    fields(n_emails = emails.len())
+)]
+pub async fn send_batch_unless_suppressed(
+    connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    emails: &[OutgoingEmail<'_>],
+) -> Result<Vec<Result<Delivery, SendEmailError>>, sqlx::Error> {
+    let recipients: Vec<&str> = emails
+        .iter()
+        .map(|email| email.recipient.as_ref())
+        .collect();
+    let suppressed = suppressed_among(connection_pool, &recipients).await?;
+    let is_suppressed =
+        |email: &OutgoingEmail<'_>| suppressed.contains(&email.recipient.as_ref().to_lowercase());
+
+    let to_send: Vec<OutgoingEmail<'_>> = emails
+        .iter()
+        .filter(|email| !is_suppressed(email))
+        .copied()
+        .collect();
+    let mut sent = email_client.send_batch(&to_send).await.into_iter();
+
+    Ok(emails
+        .iter()
+        .map(|email| {
+            if is_suppressed(email) {
+                tracing::info!(
+                    recipient = %email.recipient.as_ref(),
+                    "The recipient is on the suppression list. Not sending"
+                );
+                Ok(Delivery::Suppressed)
+            } else {
+                sent.next()
+                    .unwrap_or_else(|| {
+                        Err(SendEmailError::Unconfirmed(anyhow::anyhow!(
+                            "the email client returned fewer results than emails in the batch"
+                        )))
+                    })
+                    .map(|()| Delivery::Sent)
+            }
+        })
+        .collect())
+}
+
+/// Adds `email` to the suppression list. Returns `false` if it was already there, in which
+/// case it keeps its original reason and source.
+#[tracing::instrument(name = "Suppress email address", skip(transaction))]
+pub async fn suppress_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    reason: &str,
+    source: SuppressionSource,
+) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query!(
+        r#"INSERT INTO suppressed_emails(email, reason, source, created_at)
+        VALUES(lower($1), $2, $3, $4)
+        ON CONFLICT (email) DO NOTHING"#,
+        email,
+        reason,
+        source.as_str(),
+        Utc::now()
+    )
+    .execute(transaction)
+    .await?
+    .rows_affected();
+
+    Ok(inserted > 0)
+}
+
+/// Suppresses the address and moves its subscribers, if there are any, to `status`. Emails
+/// are only unique case-sensitively, so the same address may have been subscribed more than
+/// once. Returns `false` if the address was already suppressed.
+#[tracing::instrument(name = "Suppress subscriber", skip(transaction))]
+pub async fn suppress_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    status: SubscriptionStatus,
+    reason: &str,
+    source: SuppressionSource,
+) -> Result<bool, SubscriptionStatusError> {
+    let suppressed = suppress_email(transaction, email, reason, source).await?;
+
+    let subscribers = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE lower(email) = lower($1)"#,
+        email
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    for subscriber in subscribers {
+        match change_subscription_status(transaction, subscriber.id, status, reason).await {
+            Ok(_) => {}
+            // e.g. a bounce arriving after a complaint; the address is suppressed either way.
+            Err(SubscriptionStatusError::IllegalTransition(e)) => {
+                tracing::info!(error.message = %e, "Keeping the current subscription status");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(suppressed)
+}
+
+/// Takes `email` off the suppression list. Returns `false` if it was not on it.
+#[tracing::instrument(name = "Remove suppression", skip(connection_pool))]
+pub async fn remove_suppression(
+    connection_pool: &PgPool,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    let removed = sqlx::query!(
+        r#"DELETE FROM suppressed_emails WHERE email = lower($1)"#,
+        email
+    )
+    .execute(connection_pool)
+    .await?
+    .rows_affected();
+
+    Ok(removed > 0)
+}
+
+#[tracing::instrument(name = "List suppressions", skip(connection_pool))]
+pub async fn list_suppressions(connection_pool: &PgPool) -> Result<Vec<Suppression>, sqlx::Error> {
+    sqlx::query_as!(
+        Suppression,
+        r#"SELECT email, reason, source, created_at
+        FROM suppressed_emails
+        ORDER BY created_at DESC"#
+    )
+    .fetch_all(connection_pool)
+    .await
+}
+
+#[tracing::instrument(name = "Check suppression list", skip(connection_pool))]
+pub async fn is_suppressed(connection_pool: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM suppressed_emails WHERE email = lower($1)
+        ) AS "suppressed!""#,
+        email
+    )
+    .fetch_one(connection_pool)
+    .await?;
+
+    Ok(row.suppressed)
+}
+
+/// The lowercased addresses among `emails` that are on the suppression list.
+async fn suppressed_among(
+    connection_pool: &PgPool,
+    emails: &[&str],
+) -> Result<HashSet<String>, sqlx::Error> {
+    let emails: Vec<String> = emails.iter().map(|email| email.to_lowercase()).collect();
+    let rows = sqlx::query!(
+        r#"SELECT email FROM suppressed_emails WHERE email = ANY($1)"#,
+        &emails
+    )
+    .fetch_all(connection_pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| row.email).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuppressionSource;
+
+    #[test]
+    fn sources_round_trip_through_their_string_form() {
+        for source in [
+            SuppressionSource::Manual,
+            SuppressionSource::Bounce,
+            SuppressionSource::Complaint,
+            SuppressionSource::Legal,
+        ] {
+            assert_eq!(SuppressionSource::parse(source.as_str()), Some(source));
+            assert_eq!(
+                serde_json::to_string(&source).unwrap(),
+                format!("\"{}\"", source.as_str())
+            );
+        }
+        assert_eq!(SuppressionSource::parse("unknown"), None);
+    }
+}
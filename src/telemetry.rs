@@ -1,3 +1,4 @@
+use tokio::task::JoinHandle;
 use tracing::{dispatcher::set_global_default, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
@@ -25,3 +26,12 @@ pub fn init_tracing_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to initialize logger");
     set_global_default(subscriber.into()).expect("Failed to set tracing subscriber");
 }
+
+pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || current_span.in_scope(f))
+}
@@ -0,0 +1,231 @@
+mod helper;
+
+use crate::helper::{assert_is_redirect_to, newsletter_request_body, spawn_app};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_issue_api_keys() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_api_keys(&serde_json::json!({ "name": "cms", "scopes": ["subscribers:read"] }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_issued_key_is_shown_once_and_stored_hashed() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let key = app.issue_api_key(&["subscribers:read"]).await;
+
+    // Assert
+    let listed: serde_json::Value = app.get_api_keys().await.json().await.unwrap();
+    let listed = &listed.as_array().unwrap()[0];
+    assert_eq!(listed["name"], "cms");
+    assert_eq!(listed["scopes"], serde_json::json!(["subscribers:read"]));
+    assert!(key.starts_with(listed["key_prefix"].as_str().unwrap()));
+    assert!(!listed.to_string().contains(&key));
+
+    let saved = sqlx::query!("SELECT key_hash FROM api_keys")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved API key");
+    assert!(!saved.key_hash.contains(&key));
+}
+
+#[tokio::test]
+async fn issuing_a_key_with_an_unknown_scope_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "name": "cms", "scopes": ["subscribers:delete"] }),
+            "unknown scope",
+        ),
+        (
+            serde_json::json!({ "name": "cms", "scopes": [] }),
+            "no scopes",
+        ),
+        (
+            serde_json::json!({ "name": " ", "scopes": ["subscribers:read"] }),
+            "blank name",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        // Act
+        let response = app.post_api_keys(&body).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not reject a key with {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_key_are_rejected_with_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for api_key in [None, Some("nlk_not-a-real-key")] {
+        // Act
+        let response = app.get_api_subscribers(api_key).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    }
+}
+
+#[tokio::test]
+async fn requests_with_a_key_lacking_the_scope_are_rejected_with_403() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.issue_api_key(&["newsletters:publish"]).await;
+
+    // Act
+    let response = app.get_api_subscribers(Some(&key)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_key_with_subscribers_read_lists_subscribers_and_records_its_use() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let key = app.issue_api_key(&["subscribers:read"]).await;
+
+    // Act
+    let response = app.get_api_subscribers(Some(&key)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let subscribers: serde_json::Value = response.json().await.unwrap();
+    let subscribers = subscribers.as_array().unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["status"], "confirmed");
+
+    let saved = sqlx::query!("SELECT last_used_at FROM api_keys")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved API key");
+    assert!(saved.last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn a_key_with_subscribers_write_adds_a_pending_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.issue_api_key(&["subscribers:write"]).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_api_subscribers(
+            &key,
+            &serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.status, "pending-confirmation");
+}
+
+#[tokio::test]
+async fn a_key_with_newsletters_publish_can_publish() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let key = app.issue_api_key(&["newsletters:publish"]).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletters_with_api_key(&key, &newsletter_request_body())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn publishing_with_a_key_lacking_the_scope_is_rejected_with_403() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app
+        .issue_api_key(&["subscribers:read", "subscribers:write"])
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletters_with_api_key(&key, &newsletter_request_body())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_revoked_key_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let key = app.issue_api_key(&["subscribers:read"]).await;
+    let listed: serde_json::Value = app.get_api_keys().await.json().await.unwrap();
+    let api_key_id = listed[0]["api_key_id"].as_str().unwrap();
+
+    // Act
+    let response = app.post_revoke_api_key(api_key_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    // Assert
+    let response = app.get_api_subscribers(Some(&key)).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let listed: serde_json::Value = app.get_api_keys().await.json().await.unwrap();
+    assert!(!listed[0]["revoked_at"].is_null());
+}
+
+#[tokio::test]
+async fn revoking_an_unknown_key_returns_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_revoke_api_key(&uuid::Uuid::new_v4().to_string())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,153 @@
+mod helper;
+
+use crate::helper::{assert_is_redirect_to, spawn_app};
+
+const NEW_PASSWORD: &str = "Brand-New-Passw0rd";
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_change_password_form() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_change_password().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_your_password() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": NEW_PASSWORD,
+            "new_password_check": NEW_PASSWORD,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn new_password_fields_must_match() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Try to change password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": NEW_PASSWORD,
+            "new_password_check": "Another-Passw0rd!",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>You entered two different new passwords - the field values must match.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn current_password_must_be_valid() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Try to change password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": "Wrong-Passw0rd-123",
+            "new_password": NEW_PASSWORD,
+            "new_password_check": NEW_PASSWORD,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+}
+
+#[tokio::test]
+async fn new_password_must_satisfy_the_policy() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let test_cases = vec![
+        ("Sh0rt-pass", "at least 12 characters"),
+        ("onlylowercaseletters", "mix at least 3"),
+    ];
+
+    for (new_password, error_fragment) in test_cases {
+        // Act
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": new_password,
+                "new_password_check": new_password,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/password");
+
+        // Assert
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(error_fragment),
+            "The page did not explain why `{}` was rejected.",
+            new_password
+        );
+    }
+}
+
+#[tokio::test]
+async fn changing_password_works() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Change password
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": NEW_PASSWORD,
+            "new_password_check": NEW_PASSWORD,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>Your password has been changed.</i></p>"));
+
+    // Act - Part 3 - Logout
+    let response = app.post_logout().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 4 - The old password no longer works
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 5 - The new password does
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
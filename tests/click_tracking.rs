@@ -0,0 +1,96 @@
+mod helper;
+
+use crate::helper::spawn_app;
+
+#[tokio::test]
+async fn issues_without_click_tracking_keep_their_links() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    // Act
+    let email_request = app.publish_and_deliver(false).await;
+
+    // Assert
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html = email["HtmlContent"].as_str().unwrap();
+    let text = email["TextContent"].as_str().unwrap();
+    assert!(html.contains(r#"href="https://example.com/story""#));
+    assert!(text.contains("https://example.com/story"));
+    assert!(app.tracking_links(html, "/t/c/").is_empty());
+}
+
+#[tokio::test]
+async fn tracked_links_redirect_to_their_target_and_are_counted() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let email_request = app.publish_and_deliver(true).await;
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html = email["HtmlContent"].as_str().unwrap();
+    let text = email["TextContent"].as_str().unwrap();
+    let html_links = app.tracking_links(html, "/t/c/");
+    let text_links = app.tracking_links(text, "/t/c/");
+    assert_eq!(html_links.len(), 1);
+    assert_eq!(text_links.len(), 1);
+    assert!(!html.contains("https://example.com/story"));
+    assert!(html.contains("/subscriptions/unsubscribe?token="));
+
+    // Act
+    for link in html_links.iter().chain(&text_links) {
+        let response = app.api_client.get(link.clone()).send().await.unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 302);
+        assert_eq!(
+            response.headers()["Location"].to_str().unwrap(),
+            "https://example.com/story"
+        );
+    }
+
+    app.test_user.login(&app).await;
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let clicks: serde_json::Value = app
+        .get_issue_clicks(newsletter_issue_id)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        clicks,
+        serde_json::json!([{"url": "https://example.com/story", "clicks": 2, "subscribers": 1}])
+    );
+}
+
+#[tokio::test]
+async fn invalid_tracking_links_are_rejected_with_404() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/t/c/not-a-valid-token", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn click_reports_require_login() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_issue_clicks(uuid::Uuid::new_v4()).await;
+
+    // Assert
+    helper::assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,278 @@
+mod helper;
+
+use crate::helper::{spawn_app, TestApp};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+fn hard_bounce(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "HardBounce",
+        "TypeCode": 1,
+        "Email": email,
+        "Description": "The server was unable to deliver your message (ex: unknown user, mailbox not found).",
+        "BouncedAt": "2024-05-27T09:00:00Z"
+    })
+}
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription")
+        .status
+}
+
+async fn suppressed_sources(app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT source FROM suppressed_emails")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.source)
+        .collect()
+}
+
+#[tokio::test]
+async fn email_events_without_valid_credentials_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let url = format!("{}/webhooks/email-events", app.address);
+
+    // Act
+    let anonymous = reqwest::Client::new()
+        .post(&url)
+        .json(&hard_bounce("ursula_le_guin@gmail.com"))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let wrong_password = reqwest::Client::new()
+        .post(&url)
+        .basic_auth(&app.email_webhook_settings.username, Some("wrong-password"))
+        .json(&hard_bounce("ursula_le_guin@gmail.com"))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    for response in [anonymous, wrong_password] {
+        assert_eq!(401, response.status().as_u16());
+        assert_eq!(
+            r#"Basic realm="email-events""#,
+            response.headers()["WWW-Authenticate"]
+        );
+    }
+    assert!(suppressed_sources(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn malformed_payloads_are_rejected_with_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce"
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn a_hard_bounce_marks_the_subscriber_bounced_and_suppresses_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    // Act
+    let response = app
+        .post_email_event(&hard_bounce("Newsletter-API@gmail.com"))
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(subscriber_status(&app).await, "bounced");
+    assert_eq!(suppressed_sources(&app).await, ["bounce"]);
+    let transition = sqlx::query!(
+        "SELECT reason FROM subscription_status_transitions WHERE to_status = 'bounced'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(transition.reason.starts_with("HardBounce: "));
+}
+
+#[tokio::test]
+async fn a_hard_bounce_marks_every_subscription_of_the_address_bounced() {
+    // Arrange
+    let app = spawn_app().await;
+    for email in ["Ursula@example.com", "ursula@example.com"] {
+        sqlx::query!(
+            r#"INSERT INTO subscriptions(id, email, name, subscribed_at, status)
+            VALUES($1, $2, 'reader', now(), 'confirmed')"#,
+            uuid::Uuid::new_v4(),
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to insert subscriber");
+    }
+
+    // Act
+    let response = app
+        .post_email_event(&hard_bounce("ursula@example.com"))
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let statuses: Vec<String> = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.status)
+        .collect();
+    assert_eq!(statuses, ["bounced", "bounced"]);
+}
+
+#[tokio::test]
+async fn a_soft_bounce_is_recorded_without_affecting_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    // Act
+    let response = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "SoftBounce",
+            "Email": "newsletter-api@gmail.com",
+            "Description": "Mailbox full"
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+    assert!(suppressed_sources(&app).await.is_empty());
+    let event = sqlx::query!("SELECT event_type, payload FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event.event_type, "bounce");
+    assert_eq!(event.payload["Type"], "SoftBounce");
+}
+
+#[tokio::test]
+async fn a_spam_complaint_marks_the_subscriber_complained_and_suppresses_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    // Act
+    let response = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Email": "newsletter-api@gmail.com"
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(subscriber_status(&app).await, "complained");
+    assert_eq!(suppressed_sources(&app).await, ["complaint"]);
+}
+
+#[tokio::test]
+async fn a_bounce_after_a_complaint_keeps_the_complaint() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.post_email_event(&serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Email": "newsletter-api@gmail.com"
+    }))
+    .await;
+
+    // Act
+    let response = app
+        .post_email_event(&hard_bounce("newsletter-api@gmail.com"))
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(subscriber_status(&app).await, "complained");
+    assert_eq!(suppressed_sources(&app).await, ["complaint"]);
+}
+
+#[tokio::test]
+async fn deliveries_and_unknown_events_are_acknowledged() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    // Act
+    let delivery = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "Delivery",
+            "Recipient": "newsletter-api@gmail.com",
+            "DeliveredAt": "2024-05-27T09:00:00Z"
+        }))
+        .await;
+    let unknown = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "SubscriptionChange",
+            "Recipient": "newsletter-api@gmail.com"
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(200, delivery.status().as_u16());
+    assert_eq!(200, unknown.status().as_u16());
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+    let event_types: Vec<String> = sqlx::query!("SELECT event_type FROM email_events")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.event_type)
+        .collect();
+    assert_eq!(event_types, ["delivery"]);
+}
+
+#[tokio::test]
+async fn the_delivery_worker_skips_suppressed_addresses() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    sqlx::query!(
+        r#"INSERT INTO suppressed_emails(email, reason, source, created_at)
+        VALUES('newsletter-api@gmail.com', 'legal request', 'manual', now())"#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    }))
+    .await
+    .expect("Failed to execute request");
+
+    // Assert
+    app.dispatch_all_pending_emails().await;
+}
@@ -0,0 +1,98 @@
+mod helper;
+
+use crate::helper::{spawn_app, spawn_app_with_email_sender};
+use newsletter_api::configuration::EmailTemplateSettings;
+use newsletter_api::email_client::InMemoryEmailClient;
+use newsletter_api::email_templates::{ConfirmationEmail, EmailTemplates, TemplateError};
+use std::sync::Arc;
+
+fn template_settings() -> EmailTemplateSettings {
+    EmailTemplateSettings {
+        directory: "templates/email".into(),
+    }
+}
+
+#[tokio::test]
+async fn confirmation_emails_escape_the_subscriber_name() {
+    // Arrange
+    let email_client = Arc::new(InMemoryEmailClient::new());
+    let app = spawn_app_with_email_sender(email_client.clone()).await;
+    let body = "name=Tom%20%26%20Jerry%27s&email=newsletter-api%40gmail.com";
+
+    // Act
+    app.post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    let sent_emails = email_client.sent_emails();
+    assert_eq!(sent_emails.len(), 1);
+    assert!(sent_emails[0]
+        .html_content
+        .contains("Hi Tom &amp; Jerry's,"));
+    assert!(sent_emails[0].text_content.contains("Hi Tom & Jerry's,"));
+}
+
+#[tokio::test]
+async fn templates_in_the_database_replace_the_ones_on_disk() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"INSERT INTO email_templates (name, source) VALUES ($1, $2)"#,
+        "confirmation.html",
+        r#"<style>p { color: #333 }</style>
+        <p>Welcome aboard {{ subscriber_name }}: <a href="{{ confirmation_link }}">confirm</a>
+        or read <a href="/about">about us</a>.</p>"#,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let templates = EmailTemplates::load(&template_settings(), &app.base_url, &app.db_pool)
+        .await
+        .unwrap();
+
+    // Assert
+    let email = templates
+        .render(&ConfirmationEmail {
+            subscriber_name: "jk",
+            confirmation_link: "https://example.com/confirm",
+        })
+        .unwrap();
+    assert!(email
+        .html
+        .contains(r#"<p style="color: #333;">Welcome aboard jk:"#));
+    assert!(!email.html.contains("<style>"));
+    assert!(email.html.contains(&format!(
+        r#"<a href="{}/about" rel="noopener noreferrer">"#,
+        app.base_url
+    )));
+    assert_eq!(
+        email.text,
+        format!(
+            "Welcome aboard jk: confirm (https://example.com/confirm) or read about us ({}/about).",
+            app.base_url
+        )
+    );
+}
+
+#[tokio::test]
+async fn broken_templates_in_the_database_are_rejected_when_loading() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"INSERT INTO email_templates (name, source) VALUES ($1, $2)"#,
+        "newsletter.html",
+        "{{ unknown_variable }}",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let result = EmailTemplates::load(&template_settings(), &app.base_url, &app.db_pool).await;
+
+    // Assert
+    assert!(matches!(result, Err(TemplateError::InvalidTemplate(_))));
+}
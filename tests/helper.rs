@@ -1,4 +1,15 @@
-use newsletter_api::configuration::DatabaseSettings;
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use newsletter_api::authentication::{compute_password_hash, totp_code};
+use newsletter_api::clock::FixedClock;
+use newsletter_api::configuration::{
+    DatabaseSettings, EmailTransportSettings, EmailWebhookSettings, IssueDeliverySettings,
+    SessionStoreKind, Settings, TrackingSettings,
+};
+use newsletter_api::email_client::EmailSender;
+use newsletter_api::email_templates::EmailTemplates;
+use newsletter_api::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
 use newsletter_api::startup::{get_connection_pool, Application};
 use newsletter_api::{
     configuration::get_configuration,
@@ -6,51 +17,699 @@ use newsletter_api::{
 };
 use once_cell::sync::Lazy;
 use reqwest::{Error, Response};
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::io::sink;
+use std::sync::Arc;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub password: String,
+}
+
+impl TestUser {
+    pub fn generate() -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
+            password: Uuid::new_v4().to_string(),
+        }
+    }
+
+    async fn store(&self, connection_pool: &PgPool) {
+        let password_hash =
+            compute_password_hash(Secret::new(self.password.clone())).expect("Failed to hash");
+        sqlx::query!(
+            "INSERT INTO users (user_id, username, email, password_hash) VALUES ($1, $2, $3, $4)",
+            self.user_id,
+            self.username,
+            self.email,
+            password_hash.expose_secret(),
+        )
+        .execute(connection_pool)
+        .await
+        .expect("Failed to store test user");
+    }
+
+    pub async fn login(&self, app: &TestApp) {
+        app.post_login(&serde_json::json!({
+            "username": &self.username,
+            "password": &self.password
+        }))
+        .await;
+    }
+}
 
 pub struct TestApp {
     pub address: String,
+    pub port: u16,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub email_client: Arc<dyn EmailSender>,
+    pub email_templates: Arc<EmailTemplates>,
+    pub issue_delivery_settings: IssueDeliverySettings,
+    pub tracking_settings: TrackingSettings,
+    pub base_url: String,
+    pub hmac_secret: Secret<String>,
+    pub email_webhook_settings: EmailWebhookSettings,
+    /// The instant the application's clock is stopped at.
+    pub now: DateTime<Utc>,
+    pub test_user: TestUser,
+    pub api_client: reqwest::Client,
+}
+
+pub struct ConfirmationLinks {
+    pub html: reqwest::Url,
+    pub plain_text: reqwest::Url,
 }
 
 impl TestApp {
     pub async fn post_subscription(&self, body: String) -> Result<Response, Error> {
-        reqwest::Client::new()
+        self.api_client
             .post(format!("{}/subscriptions", self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
             .await
     }
+
+    pub async fn post_newsletters(&self, body: serde_json::Value) -> Result<Response, Error> {
+        self.post_newsletters_with_idempotency_key(body, &Uuid::new_v4().to_string())
+            .await
+    }
+
+    pub async fn post_newsletters_with_idempotency_key(
+        &self,
+        body: serde_json::Value,
+        idempotency_key: &str,
+    ) -> Result<Response, Error> {
+        self.api_client
+            .post(format!("{}/newsletters", self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .header("Idempotency-Key", idempotency_key)
+            .json(&body)
+            .send()
+            .await
+    }
+
+    /// Waits for `count` emails to reach the mock server, for those sent in the background.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<Request> {
+        for _ in 0..100 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("Expected {} emails to be sent", count);
+    }
+
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                self.email_client.as_ref(),
+                &self.email_templates,
+                &self.issue_delivery_settings,
+                &self.tracking_settings,
+                &self.base_url,
+                &self.hmac_secret,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+
+        // The background worker may still hold a task it dequeued before us.
+        while sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue WHERE next_attempt_at <= now()"#
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .unwrap()
+            > 0
+        {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_login_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_admin_dashboard(&self) -> Response {
+        self.api_client
+            .get(format!("{}/admin/dashboard", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_admin_dashboard_html(&self) -> String {
+        self.get_admin_dashboard().await.text().await.unwrap()
+    }
+
+    pub async fn post_logout(&self) -> Response {
+        self.api_client
+            .post(format!("{}/admin/logout", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_password(&self) -> Response {
+        self.api_client
+            .get(format!("{}/admin/password", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_password_html(&self) -> String {
+        self.get_change_password().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/password", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_password_reset_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/password_reset", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password_reset", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_password_reset_confirm(&self, token: &str) -> Response {
+        self.api_client
+            .get(format!("{}/password_reset/confirm", self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password_reset/confirm", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_two_factor_settings(&self) -> Response {
+        self.api_client
+            .get(format!("{}/admin/two_factor", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_two_factor_settings_html(&self) -> String {
+        self.get_two_factor_settings().await.text().await.unwrap()
+    }
+
+    pub async fn post_two_factor(&self, action: &str, body: &serde_json::Value) -> Response {
+        self.api_client
+            .post(format!("{}/admin/two_factor/{}", self.address, action))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_login_two_factor(&self) -> Response {
+        self.api_client
+            .get(format!("{}/login/two_factor", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_login_two_factor(&self, code: &str) -> Response {
+        self.api_client
+            .post(format!("{}/login/two_factor", self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Enrolls the logged-in user in two-factor authentication and returns the TOTP secret
+    /// together with the recovery codes shown on confirmation.
+    pub async fn enroll_in_two_factor(&self) -> (Secret<String>, Vec<String>) {
+        self.post_two_factor("enroll", &serde_json::json!({})).await;
+        let html_page = self.get_two_factor_settings_html().await;
+        let secret =
+            Secret::new(extract_between(&html_page, "Secret: <code>", "</code>").remove(0));
+
+        let code = totp_code(&secret, self.now).unwrap();
+        let response = self
+            .post_two_factor("confirm", &serde_json::json!({ "code": code }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        let recovery_codes = extract_between(
+            &response.text().await.unwrap(),
+            "<li><code>",
+            "</code></li>",
+        );
+
+        (secret, recovery_codes)
+    }
+
+    /// Logs in as the test user and issues an API key with the given scopes.
+    pub async fn issue_api_key(&self, scopes: &[&str]) -> String {
+        self.test_user.login(self).await;
+        let response = self
+            .post_api_keys(&serde_json::json!({ "name": "cms", "scopes": scopes }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+        let body: serde_json::Value = response.json().await.unwrap();
+        body["key"].as_str().unwrap().to_string()
+    }
+
+    pub async fn post_api_keys(&self, body: &serde_json::Value) -> Response {
+        self.api_client
+            .post(format!("{}/admin/api_keys", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_api_keys(&self) -> Response {
+        self.api_client
+            .get(format!("{}/admin/api_keys", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_revoke_api_key(&self, api_key_id: &str) -> Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/api_keys/{}/revoke",
+                self.address, api_key_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Machine clients do not keep cookies, so these use a fresh client.
+    pub async fn get_api_subscribers(&self, api_key: Option<&str>) -> Response {
+        let mut request = reqwest::Client::new().get(format!("{}/api/subscribers", self.address));
+        if let Some(api_key) = api_key {
+            request = request.bearer_auth(api_key);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
+    pub async fn post_api_subscribers(&self, api_key: &str, body: &serde_json::Value) -> Response {
+        reqwest::Client::new()
+            .post(format!("{}/api/subscribers", self.address))
+            .bearer_auth(api_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_newsletters_with_api_key(
+        &self,
+        api_key: &str,
+        body: &serde_json::Value,
+    ) -> Response {
+        reqwest::Client::new()
+            .post(format!("{}/newsletters", self.address))
+            .bearer_auth(api_key)
+            .header("Idempotency-Key", Uuid::new_v4().to_string())
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_suppressions(&self) -> Response {
+        self.api_client
+            .get(format!("{}/admin/suppressions", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_suppressions(&self, body: &serde_json::Value) -> Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_remove_suppression(&self, email: &str) -> Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions/remove", self.address))
+            .json(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Posts a webhook payload with the credentials the provider is configured with.
+    pub async fn post_email_event(&self, body: &serde_json::Value) -> Response {
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/email-events", self.address))
+            .basic_auth(
+                &self.email_webhook_settings.username,
+                Some(self.email_webhook_settings.password.expose_secret()),
+            )
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_dead_letters(&self) -> Result<Response, Error> {
+        self.api_client
+            .get(format!("{}/admin/dead_letters", self.address))
+            .send()
+            .await
+    }
+
+    pub async fn post_requeue_dead_letters(
+        &self,
+        body: serde_json::Value,
+    ) -> Result<Response, Error> {
+        self.api_client
+            .post(format!("{}/admin/dead_letters/requeue", self.address))
+            .json(&body)
+            .send()
+            .await
+    }
+
+    pub async fn get_issue_clicks(&self, newsletter_issue_id: Uuid) -> Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}/clicks",
+                self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_issue_stats(&self, newsletter_issue_id: Uuid) -> Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/{}/stats",
+                self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn create_unconfirmed_subscriber(&self) -> ConfirmationLinks {
+        let body = "name=jk&email=newsletter-api%40gmail.com";
+
+        let _mock_guard = Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .named("Create unconfirmed subscriber")
+            .expect(1)
+            .mount_as_scoped(&self.email_server)
+            .await;
+
+        self.post_subscription(body.into())
+            .await
+            .expect("Failed to execute request")
+            .error_for_status()
+            .unwrap();
+
+        let email_request = self
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        self.get_confirmation_links(&email_request)
+    }
+
+    pub async fn create_confirmed_subscriber(&self) {
+        let confirmation_links = self.create_unconfirmed_subscriber().await;
+        reqwest::get(confirmation_links.html)
+            .await
+            .expect("Failed to execute request")
+            .error_for_status()
+            .unwrap();
+    }
+
+    pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
+        let email_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+
+        let get_link = |s: &str| {
+            let links: Vec<_> = linkify::LinkFinder::new()
+                .links(s)
+                .filter(|l| *l.kind() == linkify::LinkKind::Url)
+                .collect();
+            assert_eq!(links.len(), 1);
+
+            let mut confirmation_link = reqwest::Url::parse(links[0].as_str()).unwrap();
+            assert_eq!(confirmation_link.host_str().unwrap(), "127.0.0.1");
+            confirmation_link.set_port(Some(self.port)).unwrap();
+            confirmation_link
+        };
+
+        let html = get_link(email_body["HtmlContent"].as_str().unwrap());
+        let plain_text = get_link(email_body["TextContent"].as_str().unwrap());
+        ConfirmationLinks { html, plain_text }
+    }
+}
+
+impl TestApp {
+    /// The link from the `List-Unsubscribe` header of a newsletter email, pointed at the test
+    /// server.
+    pub fn get_unsubscribe_link(&self, email_request: &wiremock::Request) -> reqwest::Url {
+        let email_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        let header = email_body["Headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["Name"] == "List-Unsubscribe")
+            .expect("missing List-Unsubscribe header");
+
+        let link = header["Value"]
+            .as_str()
+            .unwrap()
+            .trim_start_matches('<')
+            .trim_end_matches('>');
+        let mut unsubscribe_link = reqwest::Url::parse(link).unwrap();
+        assert_eq!(unsubscribe_link.host_str().unwrap(), "127.0.0.1");
+        unsubscribe_link.set_port(Some(self.port)).unwrap();
+        unsubscribe_link
+    }
+
+    /// The links in `content` to the tracking endpoint under `prefix`, `/t/c/` for clicks or
+    /// `/t/o/` for opens, pointed at the test server.
+    pub fn tracking_links(&self, content: &str, prefix: &str) -> Vec<reqwest::Url> {
+        linkify::LinkFinder::new()
+            .links(content)
+            .filter(|l| *l.kind() == linkify::LinkKind::Url && l.as_str().contains(prefix))
+            .map(|l| {
+                let mut link = reqwest::Url::parse(l.as_str()).unwrap();
+                link.set_port(Some(self.port)).unwrap();
+                link
+            })
+            .collect()
+    }
+
+    /// Publishes an issue linking to `https://example.com/story`, delivers it and returns the
+    /// email sent to the last recipient.
+    pub async fn publish_and_deliver(&self, track_clicks: bool) -> Request {
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.email_server)
+            .await;
+
+        let response = self
+            .post_newsletters(serde_json::json!({
+                "title": "Newsletter title",
+                "content": {
+                    "html": r#"<p>Read <a href="https://example.com/story">the story</a>.</p>"#,
+                    "text": "Read the story: https://example.com/story",
+                },
+                "track_clicks": track_clicks,
+            }))
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), 202);
+        self.dispatch_all_pending_emails().await;
+
+        self.email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap()
+    }
+}
+
+impl ConfirmationLinks {
+    /// The value of a query parameter on the plain text link, e.g. a token.
+    pub fn query_param(&self, name: &str) -> String {
+        self.plain_text
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_else(|| panic!("missing `{}` query parameter", name))
+    }
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    spawn(configure, None).await
+}
+
+/// Sends every email, including the worker's, through `email_client` instead of the mock
+/// email server.
+pub async fn spawn_app_with_email_sender(email_client: Arc<dyn EmailSender>) -> TestApp {
+    spawn(|_| {}, Some(email_client)).await
+}
+
+async fn spawn(
+    configure: impl FnOnce(&mut Settings),
+    email_client: Option<Arc<dyn EmailSender>>,
+) -> TestApp {
     Lazy::force(&TRACING);
     let email_server = MockServer::start().await;
     let settings = {
         let mut s = get_configuration().expect("Failed to get settings");
         s.database.database_name = Uuid::new_v4().to_string();
         s.application_port = 0;
-        s.email_client_settings.base_url = email_server.uri();
+        s.email_client_settings.transport = EmailTransportSettings::Postmark {
+            base_url: email_server.uri(),
+            auth_token: Secret::new(Uuid::new_v4().to_string()),
+        };
+        s.session_store = SessionStoreKind::InMemory;
+        // Retries are the delivery worker's job here, so mocks see exactly one request per send.
+        s.email_client_settings.connection.max_retries = 0;
+        configure(&mut s);
         s
     };
 
     configure_database(&settings.database).await;
 
-    let application = Application::build(settings.clone())
-        .await
-        .expect("Failed to spin the server");
-    let address = format!("http://127.0.0.1:{}", application.port());
+    let email_client = email_client.unwrap_or_else(|| {
+        settings
+            .email_client_settings
+            .client()
+            .expect("Failed to build the email client")
+    });
+    // Handlers that read the time see it stopped at `now`, so tests can compute time-based
+    // values such as TOTP codes for the instant the application will check them at.
+    let now = Utc::now();
+    let application = Application::build_with(
+        settings.clone(),
+        email_client.clone(),
+        Arc::new(FixedClock(now)),
+    )
+    .await
+    .expect("Failed to spin the server");
+    let port = application.port();
+    let address = format!("http://127.0.0.1:{}", port);
+    #[allow(clippy::let_underscore_future)]
     let _ = tokio::spawn(application.run_until_stoped());
-    TestApp {
+    let db_pool = get_connection_pool(&settings);
+    let email_templates = EmailTemplates::load(
+        &settings.email_template_settings,
+        &settings.application_base_url,
+        &db_pool,
+    )
+    .await
+    .expect("Failed to load the email templates");
+    let test_app = TestApp {
         address,
-        db_pool: get_connection_pool(&settings),
+        port,
+        db_pool,
         email_server,
-    }
+        email_client,
+        email_templates: Arc::new(email_templates),
+        issue_delivery_settings: settings.issue_delivery_settings.clone(),
+        tracking_settings: settings.tracking_settings,
+        base_url: settings.application_base_url.clone(),
+        hmac_secret: settings.hmac_secret.clone(),
+        email_webhook_settings: settings.email_webhook_settings.clone(),
+        now,
+        test_user: TestUser::generate(),
+        api_client: reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .build()
+            .unwrap(),
+    };
+    test_app.test_user.store(&test_app.db_pool).await;
+    test_app
 }
 
 async fn configure_database(database: &DatabaseSettings) {
@@ -61,10 +720,7 @@ async fn configure_database(database: &DatabaseSettings) {
     connection
         .execute(format!(r#"CREATE DATABASE "{}";"#, database.database_name).as_str())
         .await
-        .expect(&format!(
-            "Failed to create database: {}",
-            &database.database_name
-        ));
+        .unwrap_or_else(|_| panic!("Failed to create database: {}", &database.database_name));
 
     let connection_pool = PgPool::connect_with(database.with_db())
         .await
@@ -88,3 +744,26 @@ static TRACING: Lazy<()> = Lazy::new(|| {
         init_tracing_subscriber(subscriber);
     }
 });
+
+pub fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+pub fn assert_is_redirect_to(response: &Response, location: &str) {
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), location);
+}
+
+/// Every substring of `html` found between `start` and `end`.
+pub fn extract_between(html: &str, start: &str, end: &str) -> Vec<String> {
+    html.split(start)
+        .skip(1)
+        .map(|rest| rest.split(end).next().unwrap().to_string())
+        .collect()
+}
@@ -0,0 +1,739 @@
+mod helper;
+
+use crate::helper::{newsletter_request_body, spawn_app, spawn_app_with_email_sender};
+use newsletter_api::domain::SubscriberEmail;
+use newsletter_api::email_client::{EmailHeader, EmailSender, OutgoingEmail, SendEmailError};
+use std::sync::Arc;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, Request, ResponseTemplate};
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_unconfirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+
+    // Act
+    let response = app
+        .post_newsletters(newsletter_request_body)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(202, response.status().as_u16());
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn newsletters_are_delivered_to_confirmed_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+
+    // Act
+    let response = app
+        .post_newsletters(newsletter_request_body)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(202, response.status().as_u16());
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email
+}
+
+#[tokio::test]
+async fn newsletters_written_in_markdown_are_sent_as_html_and_plain_text() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "markdown": "Read **the [release notes](https://example.com/notes)**.<script>alert(1)</script>",
+        }
+    });
+
+    // Act
+    let response = app
+        .post_newsletters(newsletter_request_body)
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(202, response.status().as_u16());
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&email_requests.last().unwrap().body).unwrap();
+    let html_body = body["HtmlContent"].as_str().unwrap();
+    let text_body = body["TextContent"].as_str().unwrap();
+    assert!(html_body.contains(
+        r#"<p>Read <strong>the <a href="https://example.com/notes" rel="noopener noreferrer">release notes</a></strong>."#
+    ));
+    assert!(!html_body.contains("<script>"));
+    assert!(html_body.contains("Unsubscribe"));
+    assert!(text_body.contains("Read the release notes [1]."));
+    assert!(text_body.contains("[1] https://example.com/notes"));
+}
+
+#[tokio::test]
+async fn newsletters_skip_confirmed_subscribers_with_invalid_stored_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    sqlx::query!(
+        r#"INSERT INTO subscriptions(id, email, name, subscribed_at, status)
+        VALUES($1, 'not-an-email', 'broken', now(), 'confirmed')"#,
+        uuid::Uuid::new_v4()
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert invalid subscriber");
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    });
+
+    // Act
+    let response = app
+        .post_newsletters(newsletter_request_body)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(202, response.status().as_u16());
+    app.dispatch_all_pending_emails().await;
+
+    let dead_letter = sqlx::query!("SELECT subscriber_email FROM issue_delivery_dead_letters",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch dead letters");
+    assert_eq!(dead_letter.subscriber_email, "not-an-email");
+}
+
+#[tokio::test]
+async fn newsletters_returns_400_for_invalid_data() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({
+                "content": {
+                    "text": "Newsletter body as plain text",
+                    "html": "<p>Newsletter body as HTML</p>",
+                }
+            }),
+            "missing title",
+        ),
+        (
+            serde_json::json!({"title": "Newsletter!"}),
+            "missing content",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter!",
+                "content": {"html": "<p>Newsletter body as HTML</p>"}
+            }),
+            "HTML content without plain text",
+        ),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        // Act
+        let response = app
+            .post_newsletters(invalid_body)
+            .await
+            .expect("Failed to execute request");
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload was {}.",
+            error_message
+        );
+    }
+}
+
+#[tokio::test]
+async fn transient_delivery_failures_are_rescheduled_with_backoff() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = sqlx::query!(
+        r#"SELECT n_retries, next_attempt_at > now() AS "is_delayed!", last_error
+        FROM issue_delivery_queue"#,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch delivery task");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.is_delayed);
+    assert!(task.last_error.is_some());
+
+    let dead_letters = sqlx::query!("SELECT subscriber_email FROM issue_delivery_dead_letters",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch dead letters");
+    assert!(dead_letters.is_empty());
+}
+
+#[tokio::test]
+async fn permanent_delivery_failures_are_moved_to_dead_letters() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch delivery tasks");
+    assert!(queued.is_empty());
+
+    let dead_letter = sqlx::query!(
+        "SELECT subscriber_email, n_retries, last_error FROM issue_delivery_dead_letters",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch dead letters");
+    assert_eq!(dead_letter.subscriber_email, "newsletter-api@gmail.com");
+    assert_eq!(dead_letter.n_retries, 1);
+    assert!(dead_letter.last_error.contains("422"));
+}
+
+#[tokio::test]
+async fn deliveries_exhausting_their_retries_are_moved_to_dead_letters() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    sqlx::query!(
+        "UPDATE issue_delivery_queue SET n_retries = $1",
+        app.issue_delivery_settings.max_retries - 1
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to update delivery task");
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let dead_letter = sqlx::query!("SELECT n_retries FROM issue_delivery_dead_letters",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch dead letters");
+    assert_eq!(
+        dead_letter.n_retries,
+        app.issue_delivery_settings.max_retries
+    );
+}
+
+#[tokio::test]
+async fn a_sent_email_is_not_sent_again_when_recording_its_delivery_fails() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    sqlx::query!(
+        "ALTER TABLE issue_deliveries ADD CONSTRAINT reject_all CHECK (sent_at IS NULL) NOT VALID"
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to add the constraint");
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch delivery tasks");
+    assert!(queued.is_empty());
+}
+
+#[tokio::test]
+async fn failures_within_a_batch_only_affect_their_own_delivery() {
+    // Arrange
+    let app = spawn_app().await;
+    for email in ["ursula@example.com", "inactive@example.com"] {
+        sqlx::query!(
+            r#"INSERT INTO subscriptions(id, email, name, subscribed_at, status)
+            VALUES($1, $2, 'reader', now(), 'confirmed')"#,
+            uuid::Uuid::new_v4(),
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to insert subscriber");
+    }
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(|request: &Request| {
+            let messages: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+            let results: Vec<_> = messages
+                .iter()
+                .map(|message| {
+                    if message["To"] == "inactive@example.com" {
+                        serde_json::json!({ "ErrorCode": 406, "Message": "Inactive recipient" })
+                    } else {
+                        serde_json::json!({ "ErrorCode": 0, "Message": "OK" })
+                    }
+                })
+                .collect();
+            ResponseTemplate::new(200).set_body_json(results)
+        })
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch delivery tasks");
+    assert!(queued.is_empty());
+
+    let dead_letters =
+        sqlx::query!("SELECT subscriber_email, last_error FROM issue_delivery_dead_letters",)
+            .fetch_all(&app.db_pool)
+            .await
+            .expect("Failed to fetch dead letters");
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].subscriber_email, "inactive@example.com");
+    assert!(dead_letters[0].last_error.contains("406"));
+}
+
+#[tokio::test]
+async fn a_batch_with_an_unreadable_response_is_not_sent_again() {
+    // Arrange
+    let app = spawn_app().await;
+    for email in ["ursula@example.com", "octavia@example.com"] {
+        sqlx::query!(
+            r#"INSERT INTO subscriptions(id, email, name, subscribed_at, status)
+            VALUES($1, $2, 'reader', now(), 'confirmed')"#,
+            uuid::Uuid::new_v4(),
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to insert subscriber");
+    }
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>Accepted</html>"))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch delivery tasks");
+    assert!(queued.is_empty());
+
+    let dead_letters = sqlx::query!("SELECT last_error FROM issue_delivery_dead_letters",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch dead letters");
+    assert_eq!(dead_letters.len(), 2);
+    assert!(dead_letters
+        .iter()
+        .all(|d| d.last_error.contains("could not be decoded")));
+}
+
+#[tokio::test]
+async fn dead_letters_can_be_listed_and_requeued() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.test_user.login(&app).await;
+
+    let failing_mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(400))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+    drop(failing_mock_guard);
+
+    let dead_letters: serde_json::Value = app
+        .get_dead_letters()
+        .await
+        .expect("Failed to execute request")
+        .json()
+        .await
+        .expect("Failed to parse dead letters");
+    assert_eq!(dead_letters.as_array().unwrap().len(), 1);
+    let newsletter_issue_id = dead_letters[0]["newsletter_issue_id"].clone();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_requeue_dead_letters(serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+        }))
+        .await
+        .expect("Failed to execute request");
+    let requeue_body: serde_json::Value = response.json().await.unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(requeue_body["requeued"], 1);
+
+    let dead_letters: serde_json::Value = app
+        .get_dead_letters()
+        .await
+        .expect("Failed to execute request")
+        .json()
+        .await
+        .expect("Failed to parse dead letters");
+    assert!(dead_letters.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn newsletters_without_idempotency_key_are_rejected_with_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn newsletter_creation_is_idempotent() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let first_response = app
+        .post_newsletters_with_idempotency_key(newsletter_request_body(), &idempotency_key)
+        .await
+        .expect("Failed to execute request");
+    let second_response = app
+        .post_newsletters_with_idempotency_key(newsletter_request_body(), &idempotency_key)
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(202, first_response.status().as_u16());
+    assert_eq!(first_response.status(), second_response.status());
+    assert_eq!(
+        first_response.text().await.unwrap(),
+        second_response.text().await.unwrap()
+    );
+
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch newsletter issues");
+    assert_eq!(issues.len(), 1);
+    // Mock verifies on Drop that we have sent the newsletter email once
+}
+
+#[tokio::test]
+async fn concurrent_newsletter_creation_is_handled_gracefully() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let first_response =
+        app.post_newsletters_with_idempotency_key(newsletter_request_body(), &idempotency_key);
+    let second_response =
+        app.post_newsletters_with_idempotency_key(newsletter_request_body(), &idempotency_key);
+    let (first_response, second_response) = tokio::join!(first_response, second_response);
+    let first_response = first_response.expect("Failed to execute request");
+    let second_response = second_response.expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(first_response.status(), second_response.status());
+    assert_eq!(
+        first_response.text().await.unwrap(),
+        second_response.text().await.unwrap()
+    );
+    // Mock verifies on Drop that we have sent the newsletter email once
+}
+
+#[tokio::test]
+async fn requests_missing_authorization_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.address))
+        .header("Idempotency-Key", uuid::Uuid::new_v4().to_string())
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn non_existing_user_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let username = uuid::Uuid::new_v4().to_string();
+    let password = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.address))
+        .basic_auth(username, Some(password))
+        .header("Idempotency-Key", uuid::Uuid::new_v4().to_string())
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn invalid_password_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let password = uuid::Uuid::new_v4().to_string();
+    assert_ne!(app.test_user.password, password);
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", app.address))
+        .basic_auth(&app.test_user.username, Some(password))
+        .header("Idempotency-Key", uuid::Uuid::new_v4().to_string())
+        .json(&newsletter_request_body())
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+/// Sends single emails but loses the results of batches.
+struct LossyBatchSender;
+
+#[async_trait::async_trait]
+impl EmailSender for LossyBatchSender {
+    async fn send_email_with_headers(
+        &self,
+        _recipient: &SubscriberEmail,
+        _subject: &str,
+        _html_content: &str,
+        _text_content: &str,
+        _headers: &[EmailHeader<'_>],
+    ) -> Result<(), SendEmailError> {
+        Ok(())
+    }
+
+    async fn send_batch(&self, _emails: &[OutgoingEmail<'_>]) -> Vec<Result<(), SendEmailError>> {
+        Vec::new()
+    }
+}
+
+#[tokio::test]
+async fn missing_batch_results_are_dead_lettered_rather_than_sent_again() {
+    // Arrange
+    let app = spawn_app_with_email_sender(Arc::new(LossyBatchSender)).await;
+    sqlx::query!(
+        r#"INSERT INTO subscriptions(id, email, name, subscribed_at, status)
+        VALUES($1, 'ursula@example.com', 'reader', now(), 'confirmed')"#,
+        uuid::Uuid::new_v4(),
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert subscriber");
+
+    // Act
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let queued = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count delivery tasks")
+        .count;
+    assert_eq!(queued, 0);
+    let dead_letter = sqlx::query!("SELECT last_error FROM issue_delivery_dead_letters")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch dead letter");
+    assert!(dead_letter.last_error.contains("fewer results"));
+}
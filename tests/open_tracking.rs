@@ -0,0 +1,187 @@
+mod helper;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+use uuid::Uuid;
+use wiremock::Request;
+
+fn html_content(email_request: &Request) -> String {
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["HtmlContent"].as_str().unwrap().to_string()
+}
+
+async fn newsletter_issue_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+#[tokio::test]
+async fn newsletter_emails_carry_a_tracking_image_that_records_opens() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let email_request = app.publish_and_deliver(true).await;
+    let image_link = app
+        .tracking_links(&html_content(&email_request), "/t/o/")
+        .pop()
+        .expect("missing tracking image");
+
+    // Act
+    for _ in 0..2 {
+        let response = reqwest::get(image_link.clone()).await.unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["Content-Type"], "image/gif");
+        assert!(response.headers()["Cache-Control"]
+            .to_str()
+            .unwrap()
+            .contains("no-store"));
+        assert!(response.bytes().await.unwrap().starts_with(b"GIF89a"));
+    }
+
+    let open = sqlx::query!("SELECT n_opens, first_opened_at, last_opened_at FROM email_opens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(open.n_opens, 2);
+    assert!(open.first_opened_at < open.last_opened_at);
+}
+
+#[tokio::test]
+async fn tracking_can_be_disabled_globally() {
+    // Arrange
+    let app = spawn_app_with(|s| {
+        s.tracking_settings.opens = false;
+        s.tracking_settings.clicks = false;
+    })
+    .await;
+    app.create_confirmed_subscriber().await;
+
+    // Act
+    let email_request = app.publish_and_deliver(true).await;
+
+    // Assert
+    let html = html_content(&email_request);
+    assert!(app.tracking_links(&html, "/t/o/").is_empty());
+    assert!(html.contains(r#"href="https://example.com/story""#));
+}
+
+#[tokio::test]
+async fn invalid_tracking_images_are_rejected_with_404() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/t/o/not-a-valid-token.gif", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn issue_stats_count_the_engagement_of_recipients() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let email_request = app.publish_and_deliver(true).await;
+    let html = html_content(&email_request);
+
+    app.post_email_event(&serde_json::json!({
+        "RecordType": "Delivery",
+        "Recipient": "newsletter-api@gmail.com",
+    }))
+    .await;
+    reqwest::get(app.tracking_links(&html, "/t/o/").remove(0))
+        .await
+        .unwrap();
+    app.api_client
+        .get(app.tracking_links(&html, "/t/c/").remove(0))
+        .send()
+        .await
+        .unwrap();
+    reqwest::Client::new()
+        .post(app.get_unsubscribe_link(&email_request))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.get_issue_stats(newsletter_issue_id(&app).await).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let stats: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        stats,
+        serde_json::json!({
+            "sent": 1,
+            "delivered": 1,
+            "bounced": 0,
+            "opened": 1,
+            "clicked": 1,
+            "unsubscribed": 1,
+            "rates": {
+                "delivered": 1.0,
+                "bounced": 0.0,
+                "opened": 1.0,
+                "clicked": 1.0,
+                "unsubscribed": 1.0,
+            },
+        })
+    );
+}
+
+#[tokio::test]
+async fn events_before_an_issue_was_sent_do_not_count_for_it() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.post_email_event(&serde_json::json!({
+        "RecordType": "Delivery",
+        "Recipient": "newsletter-api@gmail.com",
+    }))
+    .await;
+    app.publish_and_deliver(true).await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.get_issue_stats(newsletter_issue_id(&app).await).await;
+
+    // Assert
+    let stats: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(stats["sent"], 1);
+    assert_eq!(stats["delivered"], 0);
+}
+
+#[tokio::test]
+async fn stats_of_unknown_issues_are_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.get_issue_stats(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn issue_stats_require_login() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_issue_stats(Uuid::new_v4()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
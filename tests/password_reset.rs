@@ -0,0 +1,182 @@
+mod helper;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const NEW_PASSWORD: &str = "Brand-New-Passw0rd";
+
+async fn request_reset_token(app: &TestApp) -> String {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset(&serde_json::json!({ "username": &app.test_user.username }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let email_request = &app.wait_for_emails(1).await[0];
+    let links = app.get_confirmation_links(email_request);
+    assert_eq!(links.html, links.plain_text);
+    assert_eq!(links.plain_text.path(), "/password_reset/confirm");
+    links.query_param("token")
+}
+
+fn reset_body(token: &str) -> serde_json::Value {
+    serde_json::json!({
+        "token": token,
+        "new_password": NEW_PASSWORD,
+        "new_password_check": NEW_PASSWORD,
+    })
+}
+
+#[tokio::test]
+async fn requesting_a_reset_emails_a_link_to_the_user() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    request_reset_token(&app).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], app.test_user.email);
+}
+
+#[tokio::test]
+async fn only_a_hash_of_the_reset_token_is_stored() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let token = request_reset_token(&app).await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT token_hash, user_id FROM password_reset_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved token");
+    assert_eq!(saved.user_id, app.test_user.user_id);
+    assert_ne!(saved.token_hash, token);
+    assert!(!saved.token_hash.contains(&token));
+}
+
+#[tokio::test]
+async fn unknown_usernames_get_the_same_response_and_no_email() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_password_reset(&serde_json::json!({ "username": "no-such-user" }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("a password reset link is on its way"));
+}
+
+#[tokio::test]
+async fn a_reset_link_sets_a_new_password() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+
+    // Act - Part 1 - Open the link
+    let response = app.get_password_reset_confirm(&token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 2 - Submit the new password
+    let response = app.post_password_reset_confirm(&reset_body(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your password has been reset. You can now log in.</i></p>"));
+
+    // Act - Part 3 - Login with the new password
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    let response = app.post_password_reset_confirm(&reset_body(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act
+    let response = app.post_password_reset_confirm(&reset_body(&token)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password_reset");
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains("This password reset link is invalid or has expired."));
+    let response = app.get_password_reset_confirm(&token).await;
+    assert_is_redirect_to(&response, "/password_reset");
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.post_password_reset_confirm(&reset_body(&token)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password_reset");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_weak_password_keeps_the_reset_link_valid() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+
+    // Act
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": &token,
+            "new_password": "weak",
+            "new_password_check": "weak",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/password_reset/confirm?token={}", token),
+    );
+    let response = app.post_password_reset_confirm(&reset_body(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -1,6 +1,8 @@
 mod helper;
 
-use crate::helper::spawn_app;
+use crate::helper::{spawn_app, spawn_app_with_email_sender};
+use newsletter_api::email_client::InMemoryEmailClient;
+use std::sync::Arc;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -52,6 +54,43 @@ async fn subscribe_returns_400_when_passed_with_invalid_form_data() {
     }
 }
 
+#[tokio::test]
+async fn subscribe_returns_400_when_fields_are_present_but_invalid() {
+    let app = spawn_app().await;
+    let invalid_data = vec![
+        ("name=&email=newsletter-api%40gmail.com", "invalid name"),
+        ("name=jk&email=", "invalid email"),
+        ("name=jk&email=definitely-not-an-email", "invalid email"),
+        (
+            "name=%7Bjk%7D&email=newsletter-api%40gmail.com",
+            "invalid name",
+        ),
+    ];
+
+    for (body, error_message) in invalid_data {
+        // Act
+        let response = app
+            .post_subscription(body.into())
+            .await
+            .expect("Failed to execute request");
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return 400 when the payload had an {}",
+            error_message
+        );
+        let response_body = response.text().await.expect("Failed to read response body");
+        assert!(
+            response_body.starts_with(error_message),
+            "The response body `{}` does not describe the {}",
+            response_body,
+            error_message
+        );
+    }
+}
+
 #[tokio::test]
 async fn subscribe_persist_valid_form_data() {
     let app = spawn_app().await;
@@ -103,6 +142,35 @@ async fn subscribe_sends_a_confirmation_email_for_valid_request() {
     // Mock asserts sending of email on Drop
 }
 
+#[tokio::test]
+async fn subscribe_sends_the_confirmation_email_through_the_configured_sender() {
+    // Arrange
+    let email_client = Arc::new(InMemoryEmailClient::new());
+    let app = spawn_app_with_email_sender(email_client.clone()).await;
+    let body = "name=jk&email=newsletter-api%40gmail.com";
+
+    // Act
+    let response = app
+        .post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let sent_emails = email_client.sent_emails();
+    assert_eq!(sent_emails.len(), 1);
+    assert_eq!(sent_emails[0].recipient, "newsletter-api@gmail.com");
+    assert!(sent_emails[0]
+        .text_content
+        .contains("/subscriptions/confirm?subscription_token="));
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
 #[tokio::test]
 async fn subscribe_sends_a_confirmation_email_with_link() {
     let app = spawn_app().await;
@@ -121,21 +189,192 @@ async fn subscribe_sends_a_confirmation_email_with_link() {
 
     // Assert
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let email_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    assert_eq!(confirmation_links.html, confirmation_links.plain_text)
+}
+
+#[tokio::test]
+async fn subscribe_does_not_persist_subscriber_when_confirmation_email_fails() {
+    let app = spawn_app().await;
+    let body = "name=jk&email=newsletter-api%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(500, response.status().as_u16());
+
+    let saved = sqlx::query!("SELECT email FROM subscriptions",)
+        .fetch_optional(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert!(saved.is_none());
+
+    let saved_token = sqlx::query!("SELECT subscription_token FROM subscription_tokens",)
+        .fetch_optional(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription token");
+    assert!(saved_token.is_none());
+}
+
+#[tokio::test]
+async fn subscribe_succeeds_on_retry_after_confirmation_email_failure() {
+    let app = spawn_app().await;
+    let body = "name=jk&email=newsletter-api%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
 
-    let get_link = |s: &str| {
-        let links: Vec<_> = linkify::LinkFinder::new()
-            .links(s)
-            .filter(|l| *l.kind() == linkify::LinkKind::Url)
-            .collect();
+    // Act
+    let first_response = app
+        .post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+    let second_response = app
+        .post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(500, first_response.status().as_u16());
+    assert_eq!(200, second_response.status().as_u16());
+
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.email, "newsletter-api@gmail.com");
+    assert_eq!(saved.status, "pending-confirmation");
+}
+
+#[tokio::test]
+async fn subscribe_twice_while_pending_resends_the_same_confirmation_link() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let first_response = app
+        .post_subscription("name=jk&email=newsletter-api%40gmail.com".into())
+        .await
+        .expect("Failed to execute request");
+    let second_response = app
+        .post_subscription("name=jeeva&email=newsletter-api%40gmail.com".into())
+        .await
+        .expect("Failed to execute request");
 
-        assert_eq!(links.len(), 1);
+    // Assert
+    assert_eq!(200, first_response.status().as_u16());
+    assert_eq!(200, second_response.status().as_u16());
+
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first_links = app.get_confirmation_links(&email_requests[0]);
+    let second_links = app.get_confirmation_links(&email_requests[1]);
+    assert_eq!(first_links.html, second_links.html);
 
-        links[0].as_str().to_owned()
-    };
+    let saved = sqlx::query!("SELECT name, status FROM subscriptions",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].name, "jeeva");
+    assert_eq!(saved[0].status, "pending-confirmation");
+}
 
-    let html_link = get_link(&email_body["HtmlContent"].as_str().unwrap());
-    let text_link = get_link(&email_body["TextContent"].as_str().unwrap());
+#[tokio::test]
+async fn concurrent_first_time_subscriptions_to_the_same_address_both_succeed() {
+    let app = spawn_app().await;
 
-    assert_eq!(html_link, text_link)
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let (first_response, second_response) = tokio::join!(
+        app.post_subscription("name=jk&email=newsletter-api%40gmail.com".into()),
+        app.post_subscription("name=jeeva&email=newsletter-api%40gmail.com".into()),
+    );
+
+    // Assert
+    assert_eq!(200, first_response.unwrap().status().as_u16());
+    assert_eq!(200, second_response.unwrap().status().as_u16());
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].status, "pending-confirmation");
+}
+
+#[tokio::test]
+async fn subscribe_again_after_confirmation_returns_200_without_sending_email() {
+    let app = spawn_app().await;
+    let body = "name=jk&email=newsletter-api%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .expect("Failed to execute request")
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions",)
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].status, "confirmed");
+    // Mock asserts on Drop that no second email was sent
 }
@@ -0,0 +1,196 @@
+mod helper;
+
+use crate::helper::spawn_app;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn confirmation_without_token_are_rejected_with_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/subscriptions/confirm", app.address))
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn confirmation_with_unknown_token_is_rejected_with_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=unknowntoken",
+        app.address
+    ))
+    .await
+    .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn the_link_returned_by_subscribe_returns_200_if_called() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=jk&email=newsletter-api%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act
+    let response = reqwest::get(confirmation_links.html)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=jk&email=newsletter-api%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscription(body.into())
+        .await
+        .expect("Failed to execute request");
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act
+    reqwest::get(confirmation_links.html)
+        .await
+        .expect("Failed to execute request")
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+
+    assert_eq!(saved.email, "newsletter-api@gmail.com");
+    assert_eq!(saved.name, "jk");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn every_status_change_is_recorded_with_a_reason() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    app.create_confirmed_subscriber().await;
+
+    // Assert
+    let transitions = sqlx::query!(
+        r#"SELECT from_status, to_status, reason
+        FROM subscription_status_transitions
+        ORDER BY transition_id"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch status transitions");
+
+    assert_eq!(transitions.len(), 2);
+    assert_eq!(transitions[0].from_status, None);
+    assert_eq!(transitions[0].to_status, "pending-confirmation");
+    assert_eq!(transitions[0].reason, "subscribed");
+    assert_eq!(
+        transitions[1].from_status.as_deref(),
+        Some("pending-confirmation")
+    );
+    assert_eq!(transitions[1].to_status, "confirmed");
+    assert_eq!(transitions[1].reason, "confirmation link");
+}
+
+#[tokio::test]
+async fn confirming_twice_records_a_single_transition() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = app.create_unconfirmed_subscriber().await;
+    reqwest::get(confirmation_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(confirmation_links.html)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let confirmations = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscription_status_transitions
+        WHERE to_status = 'confirmed'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(confirmations.count, 1);
+}
+
+#[tokio::test]
+async fn a_complaining_subscriber_cannot_be_confirmed_with_an_old_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = app.create_unconfirmed_subscriber().await;
+    sqlx::query!("UPDATE subscriptions SET status = 'complained'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(confirmation_links.html)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "complained");
+}
+
+#[tokio::test]
+async fn unknown_statuses_are_rejected_by_the_database() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_unconfirmed_subscriber().await;
+
+    // Act
+    let result = sqlx::query!("UPDATE subscriptions SET status = 'deleted'")
+        .execute(&app.db_pool)
+        .await;
+
+    // Assert
+    assert!(result.is_err());
+}
@@ -0,0 +1,283 @@
+mod helper;
+
+use crate::helper::{spawn_app, TestApp};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }
+    })
+}
+
+/// Publishes an issue to a single confirmed subscriber and returns the delivered email.
+async fn deliver_an_issue(app: &TestApp) -> wiremock::Request {
+    app.create_confirmed_subscriber().await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+    app.dispatch_all_pending_emails().await;
+
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn newsletters_carry_one_click_unsubscribe_headers_and_a_footer_link() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let email_request = deliver_an_issue(&app).await;
+
+    // Assert
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let headers = body["Headers"].as_array().unwrap();
+    assert!(headers.contains(&serde_json::json!({
+        "Name": "List-Unsubscribe-Post",
+        "Value": "List-Unsubscribe=One-Click"
+    })));
+
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+    assert_eq!(unsubscribe_link.path(), "/subscriptions/unsubscribe");
+    let token = unsubscribe_link
+        .query_pairs()
+        .next()
+        .unwrap()
+        .1
+        .into_owned();
+    assert!(body["HtmlContent"].as_str().unwrap().contains(&token));
+    assert!(body["TextContent"].as_str().unwrap().contains(&token));
+}
+
+#[tokio::test]
+async fn the_unsubscribe_link_shows_a_confirmation_page() {
+    // Arrange
+    let app = spawn_app().await;
+    let email_request = deliver_an_issue(&app).await;
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+
+    // Act
+    let response = reqwest::get(unsubscribe_link)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"action="/subscriptions/unsubscribe?token="#));
+
+    // Viewing the page alone must not unsubscribe, link scanners open every URL.
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn unsubscribe_links_with_a_forged_token_are_rejected_with_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let url = format!("{}/subscriptions/unsubscribe?token=forged", app.address);
+
+    // Act
+    let get_response = reqwest::get(&url).await.expect("Failed to execute request");
+    let post_response = reqwest::Client::new()
+        .post(&url)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(401, get_response.status().as_u16());
+    assert_eq!(401, post_response.status().as_u16());
+}
+
+#[tokio::test]
+async fn a_one_click_post_unsubscribes_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let email_request = deliver_an_issue(&app).await;
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_do_not_receive_later_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    let email_request = deliver_an_issue(&app).await;
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+    reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn queued_deliveries_are_skipped_after_unsubscribing() {
+    // Arrange
+    let app = spawn_app().await;
+    let email_request = deliver_an_issue(&app).await;
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn an_old_confirmation_link_does_not_resubscribe() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = app.create_unconfirmed_subscriber().await;
+    reqwest::get(confirmation_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let subscriber = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let token = newsletter_api::domain::UnsubscribeToken::sign(subscriber.id, &app.hmac_secret);
+    reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            app.address,
+            token.as_ref()
+        ))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(confirmation_links.html)
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_can_sign_up_again_and_must_reconfirm() {
+    // Arrange
+    let app = spawn_app().await;
+    let email_request = deliver_an_issue(&app).await;
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+    reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let confirmation_links = app.create_unconfirmed_subscriber().await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending-confirmation");
+
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let reasons: Vec<String> =
+        sqlx::query!("SELECT reason FROM subscription_status_transitions ORDER BY transition_id")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row.reason)
+            .collect();
+    assert_eq!(
+        reasons,
+        [
+            "subscribed",
+            "confirmation link",
+            "unsubscribe link",
+            "subscribed again",
+            "confirmation link"
+        ]
+    );
+}
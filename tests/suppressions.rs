@@ -0,0 +1,224 @@
+mod helper;
+
+use crate::helper::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const SUBSCRIBER_EMAIL: &str = "newsletter-api@gmail.com";
+
+async fn suppress(app: &TestApp, email: &str) {
+    let response = app
+        .post_suppressions(&serde_json::json!({
+            "email": email,
+            "reason": "asked us by phone",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let list = app.get_suppressions().await;
+    let add = app
+        .post_suppressions(&serde_json::json!({ "email": SUBSCRIBER_EMAIL, "reason": "test" }))
+        .await;
+    let remove = app.post_remove_suppression(SUBSCRIBER_EMAIL).await;
+
+    // Assert
+    for response in [list, add, remove] {
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[tokio::test]
+async fn suppressions_can_be_added_listed_and_removed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Add
+    let response = app
+        .post_suppressions(&serde_json::json!({
+            "email": "Ursula_Le_Guin@gmail.com",
+            "reason": "erasure request",
+            "source": "legal",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Act - Part 2 - List
+    let listed: serde_json::Value = app.get_suppressions().await.json().await.unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(listed[0]["reason"], "erasure request");
+    assert_eq!(listed[0]["source"], "legal");
+
+    // Act - Part 3 - Remove
+    let response = app
+        .post_remove_suppression("ursula_le_guin@gmail.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    let listed: serde_json::Value = app.get_suppressions().await.json().await.unwrap();
+    assert!(listed.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn invalid_suppressions_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    suppress(&app, SUBSCRIBER_EMAIL).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "email": "not-an-email", "reason": "test" }),
+            400,
+            "invalid email",
+        ),
+        (
+            serde_json::json!({ "email": "ursula@gmail.com", "reason": "  " }),
+            400,
+            "empty reason",
+        ),
+        (
+            serde_json::json!({ "email": "ursula@gmail.com", "reason": "test", "source": "rumour" }),
+            400,
+            "unknown source",
+        ),
+        (
+            serde_json::json!({ "email": SUBSCRIBER_EMAIL.to_uppercase(), "reason": "again" }),
+            409,
+            "already suppressed",
+        ),
+    ];
+
+    for (body, expected_status, description) in test_cases {
+        // Act
+        let response = app.post_suppressions(&body).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            expected_status,
+            "The API did not reject a suppression with {}.",
+            description
+        );
+    }
+    let removed_unknown = app.post_remove_suppression("ursula@gmail.com").await;
+    assert_eq!(removed_unknown.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn suppressing_an_address_marks_its_subscriber_suppressed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    suppress(&app, SUBSCRIBER_EMAIL).await;
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "suppressed");
+    let transition = sqlx::query!(
+        "SELECT reason FROM subscription_status_transitions WHERE to_status = 'suppressed'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(transition.reason, "asked us by phone");
+}
+
+#[tokio::test]
+async fn a_suppressed_address_does_not_get_a_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    suppress(&app, SUBSCRIBER_EMAIL).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscription("name=jk&email=newsletter-api%40gmail.com".into())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn a_suppressed_subscriber_signing_up_again_stays_silent_until_the_suppression_is_lifted() {
+    // Arrange
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.test_user.login(&app).await;
+    suppress(&app, SUBSCRIBER_EMAIL).await;
+
+    // Act - Part 1 - Sign up while suppressed
+    {
+        let _mock_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.post_subscription("name=jk&email=newsletter-api%40gmail.com".into())
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    // Act - Part 2 - Sign up after the suppression is lifted
+    app.post_remove_suppression(SUBSCRIBER_EMAIL).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscription("name=jk&email=newsletter-api%40gmail.com".into())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending-confirmation");
+}
+
+#[tokio::test]
+async fn password_reset_emails_are_not_sent_to_suppressed_addresses() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    suppress(&app, &app.test_user.email).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_password_reset(&serde_json::json!({ "username": &app.test_user.username }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
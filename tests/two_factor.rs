@@ -0,0 +1,335 @@
+mod helper;
+
+use crate::helper::{assert_is_redirect_to, newsletter_request_body, spawn_app, TestApp};
+use chrono::Duration;
+use newsletter_api::authentication::totp_code;
+use secrecy::Secret;
+
+fn password_login_body(app: &TestApp) -> serde_json::Value {
+    serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    })
+}
+
+// Enrollment consumes the current step, so logins in tests use the next one, which is still
+// inside the accepted drift window.
+fn next_code(app: &TestApp, secret: &Secret<String>) -> String {
+    totp_code(secret, app.now + Duration::seconds(30)).unwrap()
+}
+
+#[tokio::test]
+async fn enrollment_shows_a_provisioning_uri_and_requires_a_valid_code() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Start enrollment
+    let response = app.post_two_factor("enroll", &serde_json::json!({})).await;
+    assert_is_redirect_to(&response, "/admin/two_factor");
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains(&format!(
+        "otpauth://totp/Newsletter:{}?secret=",
+        app.test_user.username
+    )));
+
+    // Act - Part 2 - Confirm with a wrong code
+    let response = app
+        .post_two_factor("confirm", &serde_json::json!({ "code": "000000" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/two_factor");
+
+    // Assert
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("<p><i>The authentication code is invalid.</i></p>"));
+    assert!(!html_page.contains("Two-factor authentication is enabled."));
+}
+
+#[tokio::test]
+async fn confirming_enrollment_shows_recovery_codes_that_are_stored_hashed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let (_, recovery_codes) = app.enroll_in_two_factor().await;
+
+    // Assert
+    assert_eq!(recovery_codes.len(), 10);
+    let saved: Vec<String> = sqlx::query!("SELECT code_hash FROM recovery_codes")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch recovery codes")
+        .into_iter()
+        .map(|r| r.code_hash)
+        .collect();
+    assert_eq!(saved.len(), 10);
+    for code in &recovery_codes {
+        assert!(!saved.contains(code));
+    }
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("Two-factor authentication is enabled."));
+}
+
+#[tokio::test]
+async fn enrolled_users_need_a_second_step_to_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = app.enroll_in_two_factor().await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Password step
+    let response = app.post_login(&password_login_body(&app)).await;
+    assert_is_redirect_to(&response, "/login/two_factor");
+
+    // Act - Part 2 - The dashboard is still off limits
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Second step
+    let response = app.post_login_two_factor(&next_code(&app, &secret)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Assert
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn a_totp_code_cannot_be_used_twice() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = app.enroll_in_two_factor().await;
+    let code = next_code(&app, &secret);
+    app.post_logout().await;
+    app.post_login(&password_login_body(&app)).await;
+    let response = app.post_login_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // Act
+    app.post_login(&password_login_body(&app)).await;
+    let response = app.post_login_two_factor(&code).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two_factor");
+}
+
+#[tokio::test]
+async fn the_second_step_requires_a_pending_password_login() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_login_two_factor().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_send_the_user_back_to_the_password_step() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.enroll_in_two_factor().await;
+    app.post_logout().await;
+    app.post_login(&password_login_body(&app)).await;
+
+    // Act - Part 1 - A wrong code
+    let response = app.post_login_two_factor("000000").await;
+    assert_is_redirect_to(&response, "/login/two_factor");
+    let html_page = app.get_login_two_factor().await.text().await.unwrap();
+    assert!(html_page.contains("<p><i>The authentication code is invalid.</i></p>"));
+
+    // Act - Part 2 - Keep guessing
+    for _ in 0..3 {
+        app.post_login_two_factor("000000").await;
+    }
+    let response = app.post_login_two_factor("000000").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_login_two_factor().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_recovery_code_works_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = app.enroll_in_two_factor().await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Use a recovery code
+    app.post_login(&password_login_body(&app)).await;
+    let response = app.post_login_two_factor(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // Act - Part 2 - Use it again
+    app.post_login(&password_login_body(&app)).await;
+    let response = app.post_login_two_factor(&recovery_codes[0]).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two_factor");
+}
+
+#[tokio::test]
+async fn the_policy_forces_unenrolled_users_to_enroll() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_two_factor("policy", &serde_json::json!({ "require_two_factor": "on" }))
+        .await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Login
+    let response = app.post_login(&password_login_body(&app)).await;
+    assert_is_redirect_to(&response, "/admin/two_factor");
+
+    // Act - Part 2 - Other admin pages are off limits
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/admin/two_factor");
+
+    // Act - Part 3 - Enroll
+    app.enroll_in_two_factor().await;
+
+    // Assert
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn two_factor_cannot_be_disabled_while_the_policy_requires_it() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = app.enroll_in_two_factor().await;
+    app.post_two_factor("policy", &serde_json::json!({ "require_two_factor": "on" }))
+        .await;
+
+    // Act
+    let response = app
+        .post_two_factor(
+            "disable",
+            &serde_json::json!({ "code": &recovery_codes[0] }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/two_factor");
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("Two-factor authentication is required for every admin."));
+    assert!(html_page.contains("Two-factor authentication is enabled."));
+}
+
+#[tokio::test]
+async fn two_factor_can_be_disabled_with_a_valid_code() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = app.enroll_in_two_factor().await;
+
+    // Act
+    let response = app
+        .post_two_factor(
+            "disable",
+            &serde_json::json!({ "code": &recovery_codes[0] }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/two_factor");
+    app.post_logout().await;
+    let response = app.post_login(&password_login_body(&app)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_policy_applies_to_sessions_opened_before_it_was_switched_on() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    // Another admin switches the policy on.
+    sqlx::query!("UPDATE security_policy SET require_two_factor = true")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/two_factor");
+}
+
+#[tokio::test]
+async fn the_policy_cannot_be_switched_off_without_a_second_factor() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = app.enroll_in_two_factor().await;
+    app.post_two_factor("policy", &serde_json::json!({ "require_two_factor": "on" }))
+        .await;
+
+    // Act - Part 1 - Without a code
+    let response = app.post_two_factor("policy", &serde_json::json!({})).await;
+    assert_is_redirect_to(&response, "/admin/two_factor");
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("The authentication code is invalid."));
+
+    // Act - Part 2 - With a code
+    let response = app
+        .post_two_factor("policy", &serde_json::json!({ "code": &recovery_codes[0] }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/two_factor");
+    let html_page = app.get_two_factor_settings_html().await;
+    assert!(html_page.contains("Two-factor authentication is now optional."));
+}
+
+#[tokio::test]
+async fn publishing_with_a_password_is_rejected_for_enrolled_users() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.enroll_in_two_factor().await;
+
+    // Act
+    let response = app
+        .post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(issues.is_empty());
+}
+
+#[tokio::test]
+async fn publishing_with_a_password_is_rejected_while_the_policy_requires_two_factor() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!("UPDATE security_policy SET require_two_factor = true")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_newsletters(newsletter_request_body())
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}